@@ -0,0 +1,414 @@
+use crate::database::calendar::get_poke_exclusions;
+use crate::database::{
+    add_poke, data_dir, get_poke_by_name, get_poke_history, get_poke_stats, list_pokes,
+    remove_poke, update_poke, FireRecord, MissedPolicy, Poke, PokeStats, PokeStatus, Urgency,
+};
+use crate::notification::fire_poke;
+use crate::schedule::upcoming_runs;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::SqlitePool;
+use std::fs;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// File in the data directory holding the API token
+const TOKEN_FILE: &str = "api_token";
+
+/// Environment variable overriding the token file
+const TOKEN_ENV: &str = "POKE_ME_API_TOKEN";
+
+const OPENAPI: &str = include_str!("openapi.json");
+
+/// State shared by the request handlers
+#[derive(Clone)]
+struct ApiState {
+    pool: SqlitePool,
+    token: Arc<String>,
+    /// Wakes the service loop so job changes reach the scheduler right away
+    changed: Arc<Notify>,
+    started_at: DateTime<Utc>,
+}
+
+/// Error returned to API clients as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(err: impl ToString) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, err.to_string())
+    }
+
+    fn not_found(err: impl ToString) -> Self {
+        ApiError(StatusCode::NOT_FOUND, err.to_string())
+    }
+
+    fn internal(err: impl ToString) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// A job as returned by the API, with its statistics and next run
+#[derive(Serialize)]
+struct PokeView {
+    #[serde(flatten)]
+    poke: Poke,
+    schedule: String,
+    stats: PokeStats,
+    next_run: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /api/pokes`
+#[derive(Deserialize)]
+struct NewPoke {
+    name: String,
+    /// Cron expression, RRULE, "every <duration>" or "manual"
+    schedule: String,
+    detail: Option<String>,
+    #[serde(default)]
+    sound_enabled: bool,
+    #[serde(default)]
+    urgency: Urgency,
+    #[serde(default)]
+    missed_policy: MissedPolicy,
+    max_runs: Option<i64>,
+    icon: Option<String>,
+    timeout_secs: Option<i64>,
+    sound: Option<String>,
+    category: Option<String>,
+}
+
+/// Body of `PATCH /api/pokes/{name}`, absent fields are left as is and
+/// `null` clears optional ones
+#[derive(Deserialize)]
+struct PokeChanges {
+    schedule: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    detail: Option<Option<String>>,
+    sound_enabled: Option<bool>,
+    urgency: Option<Urgency>,
+    missed_policy: Option<MissedPolicy>,
+    #[serde(default, deserialize_with = "nullable")]
+    max_runs: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    icon: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    timeout_secs: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    sound: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    category: Option<Option<String>>,
+}
+
+/// Tell a present `null` apart from an absent field
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<i64>,
+}
+
+/// Serve the API on localhost until the service stops
+pub async fn serve(pool: SqlitePool, port: u16, changed: Arc<Notify>) {
+    let token = match api_token(false) {
+        Ok(token) => token,
+        Err(err) => {
+            error!("Failed to read API token, the API is disabled: {}", err);
+            return;
+        }
+    };
+    let state = ApiState {
+        pool,
+        token: Arc::new(token),
+        changed,
+        started_at: Utc::now(),
+    };
+
+    let protected = Router::new()
+        .route("/api/status", get(status))
+        .route("/api/pokes", get(list).post(create))
+        .route("/api/pokes/:name", get(show).patch(change).delete(delete))
+        .route("/api/pokes/:name/history", get(history))
+        .route("/api/pokes/:name/fire", post(fire))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate));
+    let app = Router::new()
+        .route("/api/openapi.json", get(openapi))
+        .merge(protected)
+        .with_state(state);
+
+    // Only reachable from this machine
+    let listener = match tokio::net::TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to start API on port {}: {}", port, err);
+            return;
+        }
+    };
+    info!("API listening on http://127.0.0.1:{}", port);
+    if let Err(err) = axum::serve(listener, app).await {
+        error!("API server stopped: {}", err);
+    }
+}
+
+/// The API token, created on first use. `regenerate` replaces the stored token
+pub fn api_token(regenerate: bool) -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let path = data_dir().join(TOKEN_FILE);
+    if !regenerate {
+        if let Ok(token) = fs::read_to_string(&path) {
+            if !token.trim().is_empty() {
+                return Ok(token.trim().to_string());
+            }
+        }
+    }
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    // Readable by the owner only, also when replacing an older file
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// Compare tokens without stopping at the first difference, so that the
+/// response time does not tell how much of a guess was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Reject requests without `Authorization: Bearer <token>`
+async fn authenticate(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, &state.token));
+    if !authorized {
+        return ApiError(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid API token".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
+
+async fn status(State(state): State<ApiState>) -> ApiResult<Json<serde_json::Value>> {
+    let pokes = list_pokes(&state.pool, None)
+        .await
+        .map_err(ApiError::internal)?;
+    let active = pokes
+        .iter()
+        .filter(|poke| poke.status == PokeStatus::Active)
+        .count();
+    Ok(Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "pid": std::process::id(),
+        "started_at": state.started_at,
+        "jobs": pokes.len(),
+        "active_jobs": active,
+    })))
+}
+
+async fn list(State(state): State<ApiState>) -> ApiResult<Json<Vec<PokeView>>> {
+    let pokes = list_pokes(&state.pool, None)
+        .await
+        .map_err(ApiError::internal)?;
+    let mut views = Vec::new();
+    for poke in pokes {
+        views.push(view(&state.pool, poke).await?);
+    }
+    Ok(Json(views))
+}
+
+async fn show(
+    State(state): State<ApiState>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<PokeView>> {
+    let poke = find(&state.pool, &name).await?;
+    Ok(Json(view(&state.pool, poke).await?))
+}
+
+async fn create(
+    State(state): State<ApiState>,
+    Json(new): Json<NewPoke>,
+) -> ApiResult<(StatusCode, Json<PokeView>)> {
+    let mut poke = Poke::new(new.name, new.schedule, new.detail, new.sound_enabled)
+        .map_err(ApiError::bad_request)?;
+    poke.urgency = new.urgency;
+    poke.missed_policy = new.missed_policy;
+    poke.max_runs = new.max_runs;
+    poke.icon = new.icon;
+    poke.timeout_secs = new.timeout_secs;
+    poke.sound = new.sound;
+    poke.category = new.category;
+    let name = poke.name.clone();
+
+    let sched = tokio_cron_scheduler::JobScheduler::new()
+        .await
+        .map_err(ApiError::internal)?;
+    add_poke(&state.pool, poke, &sched)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    state.changed.notify_one();
+    info!("Job '{}' added through the API", name);
+
+    let poke = find(&state.pool, &name).await?;
+    Ok((StatusCode::CREATED, Json(view(&state.pool, poke).await?)))
+}
+
+async fn change(
+    State(state): State<ApiState>,
+    Path(name): Path<String>,
+    Json(changes): Json<PokeChanges>,
+) -> ApiResult<Json<PokeView>> {
+    let mut poke = find(&state.pool, &name).await?;
+
+    if let Some(schedule) = changes.schedule {
+        poke.set_schedule(&schedule)
+            .map_err(ApiError::bad_request)?;
+    }
+    if let Some(detail) = changes.detail {
+        poke.detail = detail;
+    }
+    if let Some(sound_enabled) = changes.sound_enabled {
+        poke.sound_enabled = sound_enabled;
+    }
+    if let Some(urgency) = changes.urgency {
+        poke.urgency = urgency;
+    }
+    if let Some(missed_policy) = changes.missed_policy {
+        poke.missed_policy = missed_policy;
+    }
+    if let Some(max_runs) = changes.max_runs {
+        poke.max_runs = max_runs;
+    }
+    if let Some(icon) = changes.icon {
+        poke.icon = icon;
+    }
+    if let Some(timeout_secs) = changes.timeout_secs {
+        poke.timeout_secs = timeout_secs;
+    }
+    if let Some(sound) = changes.sound {
+        poke.sound = sound;
+    }
+    if let Some(category) = changes.category {
+        poke.category = category;
+    }
+
+    let stats = get_poke_stats(&state.pool, poke.id)
+        .await
+        .map_err(ApiError::internal)?;
+    poke.status = if poke.status == PokeStatus::Paused {
+        PokeStatus::Paused
+    } else if poke.is_exhausted(stats.run_count, Utc::now().naive_utc()) {
+        PokeStatus::Completed
+    } else {
+        PokeStatus::Active
+    };
+    update_poke(&state.pool, &poke)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    state.changed.notify_one();
+    info!("Job '{}' updated through the API", name);
+
+    let poke = find(&state.pool, &name).await?;
+    Ok(Json(view(&state.pool, poke).await?))
+}
+
+async fn delete(State(state): State<ApiState>, Path(name): Path<String>) -> ApiResult<StatusCode> {
+    remove_poke(&state.pool, &name)
+        .await
+        .map_err(|err| ApiError::not_found(err.to_string()))?;
+    state.changed.notify_one();
+    info!("Job '{}' removed through the API", name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn history(
+    State(state): State<ApiState>,
+    Path(name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<Json<Vec<FireRecord>>> {
+    let poke = find(&state.pool, &name).await?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 1000);
+    let records = get_poke_history(&state.pool, poke.id, limit)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(Json(records))
+}
+
+async fn fire(State(state): State<ApiState>, Path(name): Path<String>) -> ApiResult<StatusCode> {
+    let poke = find(&state.pool, &name).await?;
+    info!("Firing '{}' through the API", poke.name);
+    let pool = state.pool.clone();
+    tokio::spawn(async move { fire_poke(&pool, &poke, Utc::now(), 0).await });
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn find(pool: &SqlitePool, name: &str) -> ApiResult<Poke> {
+    get_poke_by_name(pool, name)
+        .await
+        .map_err(|err| ApiError::not_found(err.to_string()))
+}
+
+async fn view(pool: &SqlitePool, poke: Poke) -> ApiResult<PokeView> {
+    let stats = get_poke_stats(pool, poke.id)
+        .await
+        .map_err(ApiError::internal)?;
+    let exclusions = get_poke_exclusions(pool, poke.id)
+        .await
+        .map_err(ApiError::internal)?;
+    let next_run = upcoming_runs(&poke, &exclusions, Utc::now(), 1)
+        .ok()
+        .and_then(|runs| runs.first().copied())
+        .filter(|_| poke.status == PokeStatus::Active);
+    Ok(PokeView {
+        schedule: poke.schedule_expr(),
+        poke,
+        stats,
+        next_run,
+    })
+}
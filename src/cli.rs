@@ -1,10 +1,50 @@
 use crate::{
+    api::api_token,
+    config::{
+        self, read_table, user_config_path, Config, Overrides, KEYS, PROFILES_TABLE, SYSTEM_CONFIG,
+    },
+    crontab::{parse_crontab, read_at_jobs, read_user_crontab, SkippedEntry},
+    database::backup::{
+        backup_database, check_database, inspect_backup, restore_database, vacuum_database,
+    },
+    database::calendar::{
+        add_calendar_entry, assign_calendar, create_calendar, delete_calendar,
+        get_calendar_by_name, get_poke_calendars, get_poke_exclusions, list_calendar_entries,
+        list_calendars, remove_calendar_entry, unassign_calendar,
+    },
+    database::focus::{
+        get_active_focus_session, get_focus_totals, get_last_focus_session, save_focus_progress,
+        start_focus_session,
+    },
+    database::followup::{
+        add_followup, get_followups, get_parents, get_pending_followups, remove_followup,
+    },
+    database::journal::undo_last,
+    database::models::{parse_timeout, resolve_file_reference, FocusState},
+    database::timer::{add_timer, cancel_timer, list_timers},
+    database::trash::{list_trash, purge_poke, purge_trash, restore_poke},
     database::{
-        add_poke, get_poke_by_name, list_pokes, remove_poke, search_pokes_by_name,
-        toggle_poke_sound,
+        acknowledge_poke, add_poke, get_poke_by_name, get_poke_stats, list_pokes, pause_poke,
+        remove_poke, resume_poke, search_pokes_by_name, toggle_poke_sound, update_poke,
+        FollowUpTrigger, IntervalAnchor, MissedPolicy, Poke, PokeStatus, Urgency,
+    },
+    database::{establish_connection, SCHEMA_VERSION},
+    display::{
+        display_calendar_detail, display_calendars, display_check_report, display_config,
+        display_focus_session, display_focus_totals, display_followups, display_job_detail,
+        display_jobs, display_plan, display_rendered, display_skipped_entries, display_timers,
+        display_trash, display_upcoming,
     },
-    display::{display_job_detail, display_jobs},
-    service::{signal_refresh, stop_service},
+    focus,
+    ics::{export_pokes, parse_ics_events},
+    jobfile,
+    schedule::{
+        format_duration, format_local, parse_date, parse_duration, parse_local_datetime, to_utc,
+        upcoming_runs,
+    },
+    service::{is_service_running, signal_refresh, stop_service},
+    template::render_detail,
+    timer, tui, wizard,
 };
 use clap::{Parser, Subcommand};
 
@@ -15,10 +55,30 @@ use clap::{Parser, Subcommand};
     long_about = "A service to setup recurring notifications"
 )]
 pub struct Cli {
+    /// Override a configuration setting for this run, e.g. `--option app_name=Reminders`
+    #[arg(long = "option", global = true, value_name = "KEY=VALUE")]
+    pub options: Vec<String>,
+    /// Use this database file instead of the configured one
+    #[arg(long, global = true, value_name = "PATH")]
+    pub db: Option<String>,
+    /// Use a named profile, with its own database and service instance
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
     #[command(subcommand)]
     pub command: Commands,
 }
 
+impl Cli {
+    /// Configuration settings given on the command line
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            profile: self.profile.clone(),
+            db: self.db.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Start the background notification service
@@ -26,18 +86,72 @@ pub enum Commands {
         /// Run the service as a daemon process
         #[arg(long, default_value = "false")]
         daemon: bool,
+        /// Serve the HTTP API on localhost
+        #[arg(long, default_value = "false")]
+        api: bool,
+        /// Port of the HTTP API [default: the api_port setting, 7878]
+        #[arg(long)]
+        api_port: Option<u16>,
+        /// Apply a job file whenever it changes [default: ~/.config/poke_me/pokes.toml]
+        #[arg(long, value_name = "FILE", num_args = 0..=1)]
+        watch: Option<Option<String>>,
+        /// With --watch, also remove jobs that are not in the file
+        #[arg(long, default_value = "false", requires = "watch")]
+        prune: bool,
+        /// Export the service on the D-Bus session bus
+        #[arg(long, default_value = "false")]
+        dbus: bool,
     },
     /// Add a new scheduled notification job
     Add {
-        /// Unique name for the job
-        name: String,
-        /// Cron expression (format: "sec min hour day month weekday")
-        cron: String,
+        /// Unique name for the job. Without a schedule, the name, schedule, detail
+        /// and sound are asked for interactively
+        name: Option<String>,
+        /// Cron expression: "min hour day month weekday", with an optional leading
+        /// second and trailing year, an @macro such as @daily, an RRULE, an
+        /// interval such as "every 45m", or "manual" for follow-up only jobs
+        cron: Option<String>,
         /// Optional description or message for the notification
         detail: Option<String>,
         /// Disable notification sound for this job (sound is OFF by default)
         #[arg(long, default_value = "false")]
         sound: bool,
+        /// What to do with runs missed while the service was down: skip, once or each
+        #[arg(long, default_value = "skip")]
+        missed: MissedPolicy,
+        /// Do not fire before this local date/time ("YYYY-MM-DD" or "YYYY-MM-DD HH:MM")
+        #[arg(long)]
+        starts: Option<String>,
+        /// Complete the job after this local date/time ("YYYY-MM-DD" or "YYYY-MM-DD HH:MM")
+        #[arg(long)]
+        ends: Option<String>,
+        /// Complete the job after it fired this many times
+        #[arg(long)]
+        max_runs: Option<i64>,
+        /// Notification urgency: low, normal or critical
+        #[arg(long, default_value = "normal")]
+        urgency: Urgency,
+        /// Fire at a random time within this window after each occurrence (e.g. "2h")
+        #[arg(long)]
+        jitter: Option<String>,
+        /// Seed for the random times, to make them reproducible
+        #[arg(long)]
+        jitter_seed: Option<i64>,
+        /// What an "every" interval counts from: fixed, ack or start
+        #[arg(long, default_value = "fixed")]
+        anchor: IntervalAnchor,
+        /// Icon name from the icon theme or path to an image
+        #[arg(long)]
+        icon: Option<String>,
+        /// How long the notification stays up (e.g. "10s"), "never" to keep it until dismissed
+        #[arg(long)]
+        timeout: Option<String>,
+        /// Sound name from the sound theme or path to a sound file, enables sound
+        #[arg(long)]
+        sound_name: Option<String>,
+        /// Notification category hint, e.g. "reminder"
+        #[arg(long)]
+        category: Option<String>,
     },
     /// List all scheduled notification jobs
     List {
@@ -50,27 +164,370 @@ pub enum Commands {
         /// Exact name of the job to display
         name: String,
     },
+    /// Preview a job's notification with its detail template rendered as if it fired now
+    Render {
+        /// Exact name of the job to render
+        name: String,
+    },
     /// Search for jobs by name pattern (partial matching)
     Search {
         /// Search term to match against job names
         term: String,
     },
-    /// Remove a scheduled job by name
+    /// Move a scheduled job to the trash by name
     Remove {
         /// Name of the job to remove
         name: String,
     },
+    /// List or empty the trash of removed jobs
+    Trash {
+        #[command(subcommand)]
+        action: TrashCommands,
+    },
+    /// Take a removed job out of the trash
+    Restore {
+        /// Name of the job to restore
+        name: String,
+    },
+    /// Revert the last change made to jobs
+    Undo,
+    /// Change settings of an existing job
+    Set {
+        /// Name of the job to change
+        name: String,
+        /// What to do with runs missed while the service was down: skip, once or each
+        #[arg(long)]
+        missed: Option<MissedPolicy>,
+        /// Do not fire before this local date/time, "none" to clear
+        #[arg(long)]
+        starts: Option<String>,
+        /// Complete the job after this local date/time, "none" to clear
+        #[arg(long)]
+        ends: Option<String>,
+        /// Complete the job after it fired this many times, 0 to clear
+        #[arg(long)]
+        max_runs: Option<i64>,
+        /// Notification urgency: low, normal or critical
+        #[arg(long)]
+        urgency: Option<Urgency>,
+        /// Random delay window after each occurrence (e.g. "2h"), "none" to clear
+        #[arg(long)]
+        jitter: Option<String>,
+        /// Seed for the random times, to make them reproducible
+        #[arg(long)]
+        jitter_seed: Option<i64>,
+        /// What an "every" interval counts from: fixed, ack or start
+        #[arg(long)]
+        anchor: Option<IntervalAnchor>,
+        /// Icon name or path to an image, "none" for the default icon
+        #[arg(long)]
+        icon: Option<String>,
+        /// How long the notification stays up, "never", or "none" for the server default
+        #[arg(long)]
+        timeout: Option<String>,
+        /// Sound name or path to a sound file, "none" for the default sound
+        #[arg(long)]
+        sound_name: Option<String>,
+        /// Notification category hint, "none" to clear
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Make the jobs match a TOML job file, in the format written by `edit`
+    Apply {
+        /// Job file to apply [default: ~/.config/poke_me/pokes.toml]
+        file: Option<String>,
+        /// Also remove jobs that are not in the file
+        #[arg(long, default_value = "false")]
+        prune: bool,
+        /// Only show what would change
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Edit jobs as a TOML document in $VISUAL or $EDITOR, like `crontab -e`
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["name", "all"])))]
+    Edit {
+        /// Name of the job to edit
+        name: Option<String>,
+        /// Edit all jobs at once, jobs removed from the document are deleted
+        #[arg(long, default_value = "false", conflicts_with = "name")]
+        all: bool,
+        /// Editor command to use instead of $VISUAL or $EDITOR
+        #[arg(long)]
+        editor: Option<String>,
+    },
+    /// Acknowledge the latest notification of a job, restarting ack-anchored intervals
+    Ack {
+        /// Name of the job to acknowledge
+        name: String,
+    },
+    /// Make a job fire some time after another job fires or is acknowledged
+    Chain {
+        /// Job that starts the follow-up
+        parent: String,
+        /// Job to fire as the follow-up
+        child: String,
+        /// Delay after the parent's event (e.g. "30m")
+        #[arg(long, default_value = "0s")]
+        after: String,
+        /// Parent event that starts the follow-up: fire or ack
+        #[arg(long, default_value = "fire")]
+        on: FollowUpTrigger,
+    },
+    /// Remove the follow-up links between two jobs
+    Unchain {
+        /// Job that starts the follow-up
+        parent: String,
+        /// Job fired as the follow-up
+        child: String,
+    },
+    /// Stop a job from firing until it is resumed
+    Pause {
+        /// Name of the job to pause
+        name: String,
+    },
+    /// Resume a paused job
+    Resume {
+        /// Name of the job to resume
+        name: String,
+    },
     /// Toggle sound on/off for an existing job
     ToggleSound {
         /// Name of the job to toggle sound for
         name: String,
     },
+    /// Show upcoming notification times
+    Next {
+        /// Only show runs of this job
+        name: Option<String>,
+        /// Number of upcoming runs to show
+        #[arg(long, default_value = "5")]
+        count: usize,
+    },
+    /// Export upcoming notifications as an iCalendar (.ics) feed
+    ExportIcs {
+        /// Number of days of occurrences to expand for non-recurring exports
+        #[arg(long, default_value = "30")]
+        days: i64,
+        /// Write to this file instead of standard output
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Import notify-send entries from a crontab file (or `crontab -l`) and `at` jobs
+    ImportCrontab {
+        /// Crontab file to read instead of the current user's crontab
+        file: Option<String>,
+        /// Also import pending `at` jobs as one-off notifications
+        #[arg(long, default_value = "false")]
+        at: bool,
+        /// Only show what would be imported
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Manage exclusion calendars (holidays, vacations) that jobs skip
+    Calendar {
+        #[command(subcommand)]
+        action: CalendarCommands,
+    },
+    /// Pomodoro-style focus sessions run by the service
+    Focus {
+        #[command(subcommand)]
+        action: FocusCommands,
+    },
+    /// Start a one-off countdown timer, e.g. `timer 10m "Tea is ready"`
+    #[command(args_conflicts_with_subcommands = true)]
+    Timer {
+        #[command(subcommand)]
+        action: Option<TimerCommands>,
+        /// How long until the timer fires (e.g. "10m", "1h30m", "90")
+        duration: Option<String>,
+        /// Message shown when the timer fires
+        message: Option<String>,
+    },
+    /// Print the token for the HTTP API, creating it if needed
+    ApiToken {
+        /// Replace the token with a new one (restart the service to use it)
+        #[arg(long, default_value = "false")]
+        regenerate: bool,
+    },
+    /// Manage jobs in a full-screen terminal interface
+    Tui,
+    /// Show or change the global configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+    /// Back up, restore and check the database
+    Db {
+        #[command(subcommand)]
+        action: DbCommands,
+    },
     /// Stop the running notification service
     Stop,
-    /// Refresh the service to pick up any job changes
+    /// Force the service to reload all jobs (changes are normally picked up automatically)
     Refresh,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Show every setting and where its value comes from
+    Show,
+    /// Print the value of a setting
+    Get {
+        /// Setting to print
+        key: String,
+    },
+    /// Change a setting in the user's config file
+    Set {
+        /// Setting to change
+        key: String,
+        /// New value
+        value: String,
+        /// Write to /etc/poke_me/config.toml instead, for all users
+        #[arg(long, default_value = "false")]
+        system: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommands {
+    /// List removed jobs and when they will be purged
+    List,
+    /// Permanently delete a removed job
+    Purge {
+        /// Name of the job to purge
+        name: String,
+    },
+    /// Permanently delete every removed job
+    Empty,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommands {
+    /// Write a copy of the database, safe while the service is running
+    Backup {
+        /// File to create
+        file: String,
+    },
+    /// Replace the database with a backup
+    Restore {
+        /// Backup to restore
+        file: String,
+        /// Do not ask for confirmation
+        #[arg(long, short, default_value = "false")]
+        yes: bool,
+    },
+    /// Run SQLite's integrity check and look for orphaned rows
+    Check,
+    /// Rebuild the database file to reclaim unused space
+    Vacuum,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TimerCommands {
+    /// List pending timers with the time left
+    List,
+    /// Cancel a pending timer
+    Cancel {
+        /// ID of the timer, as shown by `timer list`
+        id: i64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FocusCommands {
+    /// Start a focus session alternating work and breaks
+    Start {
+        /// Length of a work phase
+        #[arg(long, default_value = "25m")]
+        work: String,
+        /// Length of a short break
+        #[arg(long = "break", default_value = "5m")]
+        break_time: String,
+        /// Length of the break after the last cycle, "0" to skip it
+        #[arg(long, default_value = "15m")]
+        long_break: String,
+        /// Number of work phases
+        #[arg(long, default_value = "4")]
+        cycles: i64,
+    },
+    /// Pause the current session
+    Pause,
+    /// Resume a paused session
+    Resume,
+    /// End the current session early
+    Stop,
+    /// Show the current session and today's focus time
+    Status,
+    /// Show focus time per day
+    Report {
+        /// Number of days to include
+        #[arg(long, default_value = "7")]
+        days: i64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CalendarCommands {
+    /// Create a new exclusion calendar
+    Create {
+        /// Unique name for the calendar
+        name: String,
+    },
+    /// Delete a calendar and unassign it from all jobs
+    Delete {
+        /// Name of the calendar to delete
+        name: String,
+    },
+    /// List all calendars
+    List,
+    /// Show the dates of a calendar
+    Show {
+        /// Name of the calendar to show
+        name: String,
+    },
+    /// Exclude a date or date range
+    AddDate {
+        /// Name of the calendar
+        name: String,
+        /// First excluded date (YYYY-MM-DD)
+        date: String,
+        /// Last excluded date of a range (YYYY-MM-DD), inclusive
+        #[arg(long)]
+        until: Option<String>,
+        /// Optional description, e.g. the holiday name
+        #[arg(long)]
+        summary: Option<String>,
+    },
+    /// Remove the excluded dates starting on the given date
+    RemoveDate {
+        /// Name of the calendar
+        name: String,
+        /// Start date of the entry to remove (YYYY-MM-DD)
+        date: String,
+    },
+    /// Import all events of an .ics file as excluded dates
+    Import {
+        /// Name of the calendar (created if missing)
+        name: String,
+        /// Path to the .ics file
+        file: String,
+    },
+    /// Make a job skip the dates of a calendar
+    Assign {
+        /// Name of the calendar
+        name: String,
+        /// Name of the job
+        job: String,
+    },
+    /// Stop a job from skipping the dates of a calendar
+    Unassign {
+        /// Name of the calendar
+        name: String,
+        /// Name of the job
+        job: String,
+    },
+}
+
 pub async fn handle_commands(
     command: Commands,
     pool: &sqlx::SqlitePool,
@@ -81,17 +538,69 @@ pub async fn handle_commands(
             cron,
             detail,
             sound,
+            missed,
+            starts,
+            ends,
+            max_runs,
+            urgency,
+            jitter,
+            jitter_seed,
+            anchor,
+            icon,
+            timeout,
+            sound_name,
+            category,
         } => {
             let sched = tokio_cron_scheduler::JobScheduler::new().await?;
-            let sound_enabled = sound;
-            match add_poke(pool, name, cron, detail, sound_enabled, &sched).await {
-                Ok(()) => {
-                    println!("Job added successfully");
-                    // Signal service to refresh jobs
-                    if let Err(err) = signal_refresh() {
-                        println!("Note: Service refresh failed: {}. You may need to restart the service.", err);
+            let interactive = cron.is_none();
+            let result: Result<bool, Box<dyn std::error::Error>> = async {
+                let (name, cron, detail, sound) = match cron {
+                    Some(cron) => (name.ok_or("A job name is required")?, cron, detail, sound),
+                    None => {
+                        let answers = wizard::ask_job(pool, name).await?;
+                        (
+                            answers.name,
+                            answers.schedule,
+                            answers.detail,
+                            answers.sound,
+                        )
                     }
+                };
+                let sound_enabled = sound || sound_name.is_some();
+                let mut poke = Poke::new(name, cron, detail, sound_enabled)?;
+                poke.missed_policy = missed;
+                poke.starts_at = starts
+                    .map(|value| parse_local_datetime(&value, false))
+                    .transpose()?;
+                poke.ends_at = ends
+                    .map(|value| parse_local_datetime(&value, true))
+                    .transpose()?;
+                poke.max_runs = max_runs;
+                poke.urgency = urgency;
+                poke.jitter_secs = jitter.as_deref().map(parse_duration).transpose()?;
+                if let Some(seed) = jitter_seed {
+                    poke.jitter_seed = seed;
+                }
+                poke.interval_anchor = anchor;
+                poke.icon = icon.as_deref().map(resolve_file_reference).transpose()?;
+                poke.timeout_secs = timeout.as_deref().map(parse_timeout).transpose()?.flatten();
+                poke.sound = sound_name
+                    .as_deref()
+                    .map(resolve_file_reference)
+                    .transpose()?;
+                poke.category = category;
+                if interactive && !wizard::confirm(&poke)? {
+                    return Ok(false);
+                }
+                add_poke(pool, poke, &sched).await?;
+                Ok(true)
+            }
+            .await;
+            match result {
+                Ok(true) => {
+                    println!("Job added successfully");
                 }
+                Ok(false) => println!("Job not saved"),
                 Err(err) => println!("ERROR: {}", err),
             }
         }
@@ -105,10 +614,34 @@ pub async fn handle_commands(
             }
             Err(err) => println!("ERROR: {}", err),
         },
-        Commands::Detail { name } => match get_poke_by_name(pool, &name).await {
-            Ok(poke) => {
-                display_job_detail(&poke);
+        Commands::Detail { name } => {
+            let result: Result<(), Box<dyn std::error::Error>> = async {
+                let poke = get_poke_by_name(pool, &name).await?;
+                let stats = get_poke_stats(pool, poke.id).await?;
+                let calendars = get_poke_calendars(pool, poke.id).await?;
+                let exclusions = get_poke_exclusions(pool, poke.id).await?;
+                let next_run = upcoming_runs(&poke, &exclusions, chrono::Utc::now(), 1)
+                    .ok()
+                    .and_then(|runs| runs.first().copied())
+                    .filter(|_| poke.status == PokeStatus::Active);
+                display_job_detail(&poke, &stats, &calendars, next_run);
+
+                let parents = get_parents(pool, poke.id).await?;
+                let chain = followup_chain(pool, &poke).await?;
+                let pending = get_pending_followups(pool, poke.id).await?;
+                display_followups(&parents, &chain, &pending);
+                Ok(())
+            }
+            .await;
+            if let Err(err) = result {
+                println!("ERROR: {}", err);
             }
+        }
+        Commands::Render { name } => match get_poke_by_name(pool, &name).await {
+            Ok(poke) => match render_detail(pool, &poke).await {
+                Ok(body) => display_rendered(&poke, &body),
+                Err(err) => println!("ERROR: {}", err),
+            },
             Err(err) => println!("ERROR: {}", err),
         },
         Commands::Search { term } => match search_pokes_by_name(pool, &term).await {
@@ -118,42 +651,940 @@ pub async fn handle_commands(
             }
             Err(err) => println!("ERROR: {}", err),
         },
-        Commands::Remove { name } => {
-            match remove_poke(pool, &name).await {
-                Ok(()) => {
-                    println!("Job '{}' removed successfully", name);
-                    // Signal service to refresh jobs
-                    if let Err(err) = signal_refresh() {
-                        println!("Note: Service refresh failed: {}. You may need to restart the service.", err);
-                    }
+        Commands::Remove { name } => match remove_poke(pool, &name).await {
+            Ok(()) => {
+                println!("Job '{}' moved to the trash", name);
+                println!(
+                    "Use 'poke_me restore {}' or 'poke_me undo' to bring it back",
+                    name
+                );
+            }
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Trash { action } => handle_trash_command(action, pool).await?,
+        Commands::Restore { name } => match restore_poke(pool, &name).await {
+            Ok(()) => println!("Job '{}' restored from the trash", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Undo => match undo_last(pool).await {
+            Ok(description) => println!("Undone: {}", description),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Set {
+            name,
+            missed,
+            starts,
+            ends,
+            max_runs,
+            urgency,
+            jitter,
+            jitter_seed,
+            anchor,
+            icon,
+            timeout,
+            sound_name,
+            category,
+        } => {
+            let changes = SetChanges {
+                missed,
+                starts,
+                ends,
+                max_runs,
+                urgency,
+                jitter,
+                jitter_seed,
+                anchor,
+                icon,
+                timeout,
+                sound_name,
+                category,
+            };
+            match set_poke(pool, &name, changes).await {
+                Ok(()) => println!("Job '{}' updated successfully", name),
+                Err(err) => println!("ERROR: {}", err),
+            }
+        }
+        Commands::Apply {
+            file,
+            prune,
+            dry_run,
+        } => {
+            let path = file.map_or_else(jobfile::default_path, std::path::PathBuf::from);
+            let result: Result<(), Box<dyn std::error::Error>> = async {
+                let plan = jobfile::plan_file(pool, &path, prune).await?;
+                if plan.is_empty() {
+                    println!("No changes, jobs match {}", path.display());
+                    return Ok(());
+                }
+                display_plan(&plan);
+                if !dry_run {
+                    let sched = tokio_cron_scheduler::JobScheduler::new().await?;
+                    plan.apply(pool, &sched).await?;
+                    println!("Changes applied");
                 }
+                Ok(())
+            }
+            .await;
+            if let Err(err) = result {
+                println!("ERROR: {}", err);
+            }
+        }
+        Commands::Edit { name, all, editor } => {
+            if let Err(err) = edit_jobs(pool, name, all, editor).await {
+                println!("ERROR: {}", err);
+            }
+        }
+        Commands::Chain {
+            parent,
+            child,
+            after,
+            on,
+        } => {
+            let result: Result<(), Box<dyn std::error::Error>> = async {
+                let delay_secs = parse_duration(&after)?;
+                add_followup(pool, &parent, &child, delay_secs, on).await
+            }
+            .await;
+            match result {
+                Ok(()) => println!("Job '{}' now follows up '{}'", child, parent),
+                Err(err) => println!("ERROR: {}", err),
+            }
+        }
+        Commands::Unchain { parent, child } => match remove_followup(pool, &parent, &child).await {
+            Ok(()) => println!("Job '{}' no longer follows up '{}'", child, parent),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Ack { name } => {
+            let result: Result<(), Box<dyn std::error::Error>> = async {
+                let poke = get_poke_by_name(pool, &name).await?;
+                acknowledge_poke(pool, &poke, chrono::Utc::now().naive_utc()).await?;
+                Ok(())
+            }
+            .await;
+            match result {
+                Ok(()) => println!("Job '{}' acknowledged", name),
                 Err(err) => println!("ERROR: {}", err),
             }
         }
-        Commands::ToggleSound { name } => {
-            match toggle_poke_sound(pool, &name).await {
-                Ok(sound_enabled) => {
-                    let status = if sound_enabled { "ON" } else { "OFF" };
-                    println!("Sound toggled to {} for job '{}'", status, name);
-                    // Signal service to refresh jobs
-                    if let Err(err) = signal_refresh() {
-                        println!("Note: Service refresh failed: {}. You may need to restart the service.", err);
+        Commands::Pause { name } => match pause_poke(pool, &name).await {
+            Ok(()) => println!("Job '{}' paused", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Resume { name } => match resume_poke(pool, &name).await {
+            Ok(PokeStatus::Completed) => {
+                println!(
+                    "Job '{}' ran out of runs while paused and is now completed",
+                    name
+                )
+            }
+            Ok(_) => println!("Job '{}' resumed", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::ToggleSound { name } => match toggle_poke_sound(pool, &name).await {
+            Ok(sound_enabled) => {
+                let status = if sound_enabled { "ON" } else { "OFF" };
+                println!("Sound toggled to {} for job '{}'", status, name);
+            }
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::Next { name, count } => {
+            let pokes = match name {
+                Some(name) => get_poke_by_name(pool, &name).await.map(|poke| vec![poke]),
+                None => list_pokes(pool, None).await.map_err(|err| err.into()),
+            };
+            match pokes {
+                Ok(pokes) => {
+                    let mut runs = Vec::new();
+                    for poke in pokes
+                        .iter()
+                        .filter(|poke| poke.status == PokeStatus::Active)
+                    {
+                        let stats = get_poke_stats(pool, poke.id).await?;
+                        let remaining = poke
+                            .max_runs
+                            .map_or(count, |max| (max - stats.run_count).max(0) as usize);
+                        let exclusions = get_poke_exclusions(pool, poke.id).await?;
+                        match upcoming_runs(
+                            poke,
+                            &exclusions,
+                            chrono::Utc::now(),
+                            count.min(remaining),
+                        ) {
+                            Ok(times) => {
+                                runs.extend(times.into_iter().map(|time| (time, poke.name.clone())))
+                            }
+                            Err(err) => println!("ERROR: {}", err),
+                        }
+                        // Follow-ups already queued by their parent
+                        for due_at in get_pending_followups(pool, poke.id).await? {
+                            runs.push((to_utc(due_at), poke.name.clone()));
+                        }
                     }
+                    runs.sort();
+                    runs.truncate(count);
+                    display_upcoming(&runs);
                 }
                 Err(err) => println!("ERROR: {}", err),
             }
         }
+        Commands::ExportIcs { days, output } => match export_pokes(pool, days).await {
+            Ok(calendar) => match output {
+                Some(path) => match std::fs::write(&path, calendar) {
+                    Ok(()) => println!("Calendar exported to {}", path),
+                    Err(err) => println!("ERROR: Failed to write {}: {}", path, err),
+                },
+                None => print!("{}", calendar),
+            },
+            Err(err) => println!("ERROR: {}", err),
+        },
+        Commands::ImportCrontab { file, at, dry_run } => {
+            if let Err(err) = import_crontab(pool, file, at, dry_run).await {
+                println!("ERROR: {}", err);
+            }
+        }
+        Commands::Calendar { action } => handle_calendar_command(action, pool).await?,
+        Commands::Focus { action } => {
+            if let Err(err) = handle_focus_command(action, pool).await {
+                println!("ERROR: {}", err);
+            }
+        }
+        Commands::Timer {
+            action,
+            duration,
+            message,
+        } => {
+            if let Err(err) = handle_timer_command(action, duration, message, pool).await {
+                println!("ERROR: {}", err);
+            }
+        }
+        Commands::ApiToken { regenerate } => match api_token(regenerate) {
+            Ok(token) => println!("{}", token),
+            Err(err) => println!("ERROR: {}", err),
+        },
         Commands::Stop => match stop_service() {
             Ok(()) => println!("Service stopped successfully"),
             Err(err) => println!("ERROR: {}", err),
         },
+        Commands::Tui => {
+            if let Err(err) = tui::run(pool).await {
+                println!("ERROR: {}", err);
+            }
+        }
         Commands::Refresh => match signal_refresh() {
             Ok(()) => println!("Service refresh signal sent successfully"),
             Err(err) => println!("ERROR: {}", err),
         },
-        Commands::Service { .. } => {
-            // Service command is handled separately in main.rs
-            unreachable!("Service command should be handled in main.rs");
+        Commands::Service { .. } | Commands::Config { .. } | Commands::Db { .. } => {
+            // Service, config and db commands are handled separately in main.rs
+            unreachable!("Command should be handled in main.rs");
+        }
+    }
+
+    Ok(())
+}
+
+/// Follow-ups started by a job and, recursively, by its follow-ups, as
+/// `(depth, link)` pairs in display order. Jobs already shown are not expanded
+/// again, so cyclic chains (work → break → work) terminate
+async fn followup_chain(
+    pool: &sqlx::SqlitePool,
+    poke: &Poke,
+) -> Result<Vec<(usize, crate::database::models::FollowUp)>, sqlx::Error> {
+    let mut chain = Vec::new();
+    let mut visited = vec![poke.id];
+    let mut stack: Vec<_> = get_followups(pool, poke.id)
+        .await?
+        .into_iter()
+        .rev()
+        .map(|link| (1, link))
+        .collect();
+    while let Some((depth, link)) = stack.pop() {
+        let expand = !visited.contains(&link.child_id);
+        visited.push(link.child_id);
+        if expand {
+            for child in get_followups(pool, link.child_id).await?.into_iter().rev() {
+                stack.push((depth + 1, child));
+            }
+        }
+        chain.push((depth, link));
+    }
+    Ok(chain)
+}
+
+/// Settings changed by the `set` command, `None` meaning "leave as is"
+struct SetChanges {
+    missed: Option<MissedPolicy>,
+    starts: Option<String>,
+    ends: Option<String>,
+    max_runs: Option<i64>,
+    urgency: Option<Urgency>,
+    jitter: Option<String>,
+    jitter_seed: Option<i64>,
+    anchor: Option<IntervalAnchor>,
+    icon: Option<String>,
+    timeout: Option<String>,
+    sound_name: Option<String>,
+    category: Option<String>,
+}
+
+/// Apply `set` changes to a job, reactivating or completing it as its limits now dictate
+async fn set_poke(
+    pool: &sqlx::SqlitePool,
+    name: &str,
+    changes: SetChanges,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut poke = get_poke_by_name(pool, name).await?;
+
+    if let Some(missed) = changes.missed {
+        poke.missed_policy = missed;
+    }
+    if let Some(starts) = changes.starts {
+        poke.starts_at = parse_optional_datetime(&starts, false)?;
+    }
+    if let Some(ends) = changes.ends {
+        poke.ends_at = parse_optional_datetime(&ends, true)?;
+    }
+    if let Some(max_runs) = changes.max_runs {
+        poke.max_runs = if max_runs == 0 { None } else { Some(max_runs) };
+    }
+    if let Some(urgency) = changes.urgency {
+        poke.urgency = urgency;
+    }
+    if let Some(jitter) = changes.jitter {
+        poke.jitter_secs = if jitter.eq_ignore_ascii_case("none") {
+            None
+        } else {
+            Some(parse_duration(&jitter)?)
+        };
+    }
+    if let Some(seed) = changes.jitter_seed {
+        poke.jitter_seed = seed;
+    }
+    if let Some(anchor) = changes.anchor {
+        poke.interval_anchor = anchor;
+    }
+    if let Some(icon) = changes.icon {
+        poke.icon = clearable(&icon).map(resolve_file_reference).transpose()?;
+    }
+    if let Some(timeout) = changes.timeout {
+        poke.timeout_secs = clearable(&timeout)
+            .map(parse_timeout)
+            .transpose()?
+            .flatten();
+    }
+    if let Some(sound_name) = changes.sound_name {
+        poke.sound = clearable(&sound_name)
+            .map(resolve_file_reference)
+            .transpose()?;
+        if poke.sound.is_some() {
+            poke.sound_enabled = true;
+        }
+    }
+    if let Some(category) = changes.category {
+        poke.category = clearable(&category).map(str::to_string);
+    }
+
+    // Paused jobs stay paused until resumed
+    let stats = get_poke_stats(pool, poke.id).await?;
+    poke.status = if poke.status == PokeStatus::Paused {
+        PokeStatus::Paused
+    } else if poke.is_exhausted(stats.run_count, chrono::Utc::now().naive_utc()) {
+        PokeStatus::Completed
+    } else {
+        PokeStatus::Active
+    };
+
+    update_poke(pool, &poke).await
+}
+
+/// Parse a date argument where "none" clears the value
+fn parse_optional_datetime(
+    value: &str,
+    end_of_day: bool,
+) -> Result<Option<chrono::NaiveDateTime>, String> {
+    if value.eq_ignore_ascii_case("none") {
+        Ok(None)
+    } else {
+        parse_local_datetime(value, end_of_day).map(Some)
+    }
+}
+
+/// `None` when a `set` argument is "none", meaning "back to the default"
+fn clearable(value: &str) -> Option<&str> {
+    (!value.eq_ignore_ascii_case("none")).then_some(value)
+}
+
+/// Handle `config` commands. They do not need the database and work even when
+/// the configuration is invalid, so that it can be fixed
+pub fn handle_config_command(
+    action: ConfigCommands,
+    overrides: &Overrides,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        ConfigCommands::Show => {
+            let config = Config::load(overrides)?;
+            display_config(&config);
+        }
+        ConfigCommands::Get { key } => {
+            let config = Config::load(overrides)?;
+            println!("{}", config.get(&key)?);
+        }
+        ConfigCommands::Set { key, value, system } => {
+            if !KEYS.contains(&key.as_str()) {
+                return Err(format!(
+                    "Unknown key '{}', expected one of: {}",
+                    key,
+                    KEYS.join(", ")
+                )
+                .into());
+            }
+            // Check the value the same way it is checked when loaded
+            let mut checked = Config::default();
+            checked
+                .set(&key, &value)
+                .map_err(|err| format!("Invalid value for '{}': {}", key, err))?;
+
+            let path = if system {
+                std::path::PathBuf::from(SYSTEM_CONFIG)
+            } else {
+                user_config_path()
+            };
+            let mut table = read_table(&path)?.unwrap_or_default();
+            let stored = match key.as_str() {
+                "api_port" => toml::Value::Integer(checked.api_port.into()),
+                "backup_keep" => toml::Value::Integer(checked.backup_keep as i64),
+                "trash_days" => toml::Value::Integer(checked.trash_days.into()),
+                _ => toml::Value::String(value.clone()),
+            };
+            // With a profile, the value only applies to that profile
+            let (target, location) = match &overrides.profile {
+                Some(profile) => {
+                    let profiles = table
+                        .entry(PROFILES_TABLE)
+                        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                        .as_table_mut()
+                        .ok_or_else(|| {
+                            format!("{}: '{}' is not a table", path.display(), PROFILES_TABLE)
+                        })?;
+                    let section = profiles
+                        .entry(profile.clone())
+                        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                        .as_table_mut()
+                        .ok_or_else(|| {
+                            format!(
+                                "{}: '{}.{}' is not a table",
+                                path.display(),
+                                PROFILES_TABLE,
+                                profile
+                            )
+                        })?;
+                    (
+                        section,
+                        format!("{} [{}.{}]", path.display(), PROFILES_TABLE, profile),
+                    )
+                }
+                None => (&mut table, path.display().to_string()),
+            };
+            target.insert(key.clone(), stored);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&path, toml::to_string(&table)?)
+                .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+            println!("Set {} = {} in {}", key, value, location);
+
+            let config = Config::load(overrides)?;
+            let source = config.source(&key);
+            if source != location {
+                println!("NOTE: {} overrides this value", source);
+            } else if is_service_running() {
+                println!("Restart the service for the change to take effect");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle `db` commands. `restore` opens the database itself, so that a
+/// damaged database can still be replaced
+pub async fn handle_db_command(action: DbCommands) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        DbCommands::Backup { file } => {
+            let pool = establish_connection().await?;
+            backup_database(&pool, std::path::Path::new(&file)).await?;
+            println!("Database backed up to {}", file);
+        }
+        DbCommands::Restore { file, yes } => {
+            if is_service_running() {
+                return Err("Stop the service before restoring a backup".into());
+            }
+            let path = std::path::Path::new(&file);
+            let info = inspect_backup(path).await?;
+            println!(
+                "{} holds {} job(s), schema version {} (this version: {})",
+                file, info.jobs, info.schema_version, SCHEMA_VERSION
+            );
+            let prompt = format!("Replace {} with this backup?", config::get().db.display());
+            if !yes && !wizard::ask_yes_no(&prompt, false)? {
+                println!("Database not restored");
+                return Ok(());
+            }
+            let saved = restore_database(path).await?;
+            if let Some(saved) = saved {
+                println!("Previous database saved to {}", saved.display());
+            }
+            println!("Database restored from {}", file);
+        }
+        DbCommands::Check => {
+            let pool = establish_connection().await?;
+            let report = check_database(&pool).await?;
+            display_check_report(&report);
+            if !report.integrity_errors.is_empty() {
+                return Err("The database failed the integrity check, restore a backup".into());
+            }
+        }
+        DbCommands::Vacuum => {
+            let pool = establish_connection().await?;
+            let (before, after) = vacuum_database(&pool).await?;
+            println!(
+                "Database vacuumed: {} KiB -> {} KiB",
+                before.div_ceil(1024),
+                after.div_ceil(1024)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Open a file in `editor`, or else the user's editor, and wait for it to be closed
+fn run_editor(
+    path: &std::path::Path,
+    editor: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let editor = editor
+        .map(str::to_string)
+        .into_iter()
+        .chain(
+            ["VISUAL", "EDITOR"]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok()),
+        )
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // Run through the shell, the editor may come with arguments such as "code --wait"
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .map_err(|err| format!("Failed to start editor '{}': {}", editor, err))?;
+    if !status.success() {
+        return Err(format!("Editor '{}' exited with {}", editor, status).into());
+    }
+    Ok(())
+}
+
+/// Edit one or all jobs as a TOML document and apply the result in a single
+/// transaction, reopening the editor until the document is valid or the user
+/// gives up
+async fn edit_jobs(
+    pool: &sqlx::SqlitePool,
+    name: Option<String>,
+    all: bool,
+    editor: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pokes = match name {
+        Some(name) if !all => vec![get_poke_by_name(pool, &name).await?],
+        _ => list_pokes(pool, None).await?,
+    };
+    // Only the jobs that were in the document are deleted when left out
+    let deletable: Vec<String> = pokes.iter().map(|poke| poke.name.clone()).collect();
+    let original = jobfile::to_document(&pokes)?;
+    // An unguessable name created afresh, so that no one can plant a file or
+    // symlink there beforehand, and readable by the owner only
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let path = std::env::temp_dir().join(format!("poke_me-{}.toml", uuid::Uuid::new_v4().simple()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(original.as_bytes())?;
+
+    let sched = tokio_cron_scheduler::JobScheduler::new().await?;
+    let result: Result<(), Box<dyn std::error::Error>> = async {
+        loop {
+            run_editor(&path, editor.as_deref())?;
+            let text = std::fs::read_to_string(&path)?;
+            let existing = list_pokes(pool, None).await?;
+            let errors = match jobfile::plan(&text, &existing, &deletable) {
+                Ok(plan) if plan.is_empty() => {
+                    println!("No changes");
+                    return Ok(());
+                }
+                Ok(plan) => match plan.apply(pool, &sched).await {
+                    Ok(()) => {
+                        display_plan(&plan);
+                        println!("Changes applied");
+                        return Ok(());
+                    }
+                    Err(err) => vec![err.to_string()],
+                },
+                Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+            };
+            println!("Nothing was changed:");
+            for error in errors {
+                println!("  {}", error);
+            }
+            if !wizard::ask_yes_no("Edit again?", true)? {
+                println!("Edits discarded");
+                return Ok(());
+            }
+        }
+    }
+    .await;
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Turn notify-send crontab lines and `at` jobs into jobs, or preview them with `dry_run`
+async fn import_crontab(
+    pool: &sqlx::SqlitePool,
+    file: Option<String>,
+    at: bool,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = match &file {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path, err))?,
+        None => read_user_crontab()?,
+    };
+    let (mut entries, mut skipped) = parse_crontab(&content);
+    if at {
+        let (at_entries, at_skipped) = read_at_jobs()?;
+        entries.extend(at_entries);
+        skipped.extend(at_skipped);
+    }
+
+    let existing = list_pokes(pool, None).await?;
+    let mut taken: Vec<String> = existing.iter().map(|poke| poke.name.clone()).collect();
+    let mut pokes = Vec::new();
+    for entry in entries {
+        let skip = |reason: String| SkippedEntry {
+            source: entry.source.clone(),
+            text: format!("{} {}", entry.schedule, entry.summary),
+            reason,
+        };
+        let mut poke = match Poke::new(
+            entry.summary.clone(),
+            entry.schedule.clone(),
+            entry.body.clone(),
+            false,
+        ) {
+            Ok(poke) => poke,
+            Err(err) => {
+                skipped.push(skip(err));
+                continue;
+            }
+        };
+        poke.urgency = entry.urgency;
+        poke.icon = entry.icon.clone();
+        poke.timeout_secs = entry.timeout_secs;
+        poke.category = entry.category.clone();
+        if entry.once {
+            poke.max_runs = Some(1);
+        }
+
+        // Importing the same crontab twice must not duplicate jobs. RRULEs get
+        // today's DTSTART, so only their rule is compared
+        let rule = |poke: &Poke| {
+            poke.rrule
+                .as_deref()
+                .and_then(|rrule| rrule.split_once("RRULE:"))
+                .map(|(_, rule)| rule.to_string())
+        };
+        let suffixed = format!("{} (", poke.name);
+        if existing.iter().any(|other| {
+            (other.name == poke.name || other.name.starts_with(&suffixed))
+                && other.cron == poke.cron
+                && rule(other) == rule(&poke)
+                && other.detail == poke.detail
+        }) {
+            skipped.push(skip("already imported".to_string()));
+            continue;
+        }
+        let mut suffix = 2;
+        while taken.contains(&poke.name) {
+            poke.name = format!("{} ({})", entry.summary, suffix);
+            suffix += 1;
+        }
+        taken.push(poke.name.clone());
+        pokes.push(poke);
+    }
+
+    if dry_run {
+        if pokes.is_empty() {
+            println!("Nothing to import");
+        } else {
+            display_jobs(&pokes, "Jobs to import", true);
+            println!(
+                "Crontab times are read as local time (UTC{}) and kept in local time as RRULEs",
+                chrono::Local::now().format("%:z")
+            );
+        }
+    } else {
+        let sched = tokio_cron_scheduler::JobScheduler::new().await?;
+        let mut imported = 0;
+        for poke in pokes {
+            let name = poke.name.clone();
+            match add_poke(pool, poke, &sched).await {
+                Ok(()) => imported += 1,
+                Err(err) => println!("ERROR: Failed to import '{}': {}", name, err),
+            }
+        }
+        println!("Imported {} job(s)", imported);
+    }
+    if !skipped.is_empty() {
+        println!();
+        display_skipped_entries(&skipped);
+    }
+
+    Ok(())
+}
+
+async fn handle_timer_command(
+    action: Option<TimerCommands>,
+    duration: Option<String>,
+    message: Option<String>,
+    pool: &sqlx::SqlitePool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        Some(TimerCommands::List) => display_timers(&list_timers(pool).await?),
+        Some(TimerCommands::Cancel { id }) => {
+            cancel_timer(pool, id).await?;
+            println!("Timer {} cancelled", id);
+        }
+        None => {
+            let duration = duration.ok_or("Give a duration, e.g. `timer 10m \"Tea is ready\"`")?;
+            let secs = parse_duration(&duration)?;
+            let message = message.unwrap_or_else(|| format!("{} timer", format_duration(secs)));
+            let timer = add_timer(pool, &message, secs).await?;
+            if is_service_running() {
+                println!(
+                    "Timer {} set for {} (at {})",
+                    timer.id,
+                    format_duration(secs),
+                    format_local(timer.due_at)
+                );
+            } else {
+                println!("The service is not running, keeping the timer in this terminal (Ctrl-C to cancel)");
+                timer::wait_in_foreground(pool, &timer).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_focus_command(
+    action: FocusCommands,
+    pool: &sqlx::SqlitePool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let now = chrono::Utc::now().naive_utc();
+    match action {
+        FocusCommands::Start {
+            work,
+            break_time,
+            long_break,
+            cycles,
+        } => {
+            let session = start_focus_session(
+                pool,
+                parse_duration(&work)?,
+                parse_duration(&break_time)?,
+                parse_duration(&long_break)?,
+                cycles,
+            )
+            .await?;
+            println!(
+                "Focus session started: {} cycles of {} work",
+                session.cycles, work
+            );
+            if !is_service_running() {
+                println!("WARNING: The service is not running, phase changes will not be notified");
+            }
+        }
+        FocusCommands::Pause | FocusCommands::Resume | FocusCommands::Stop => {
+            let mut session = get_active_focus_session(pool)
+                .await?
+                .ok_or("No focus session in progress")?;
+            // Account for phases that ended while the service was not looking
+            let completed_work = focus::advance(&mut session, now);
+            let message = match action {
+                FocusCommands::Pause => focus::pause(&mut session, now).map(|_| "paused"),
+                FocusCommands::Resume => focus::resume(&mut session, now).map(|_| "resumed"),
+                _ => {
+                    focus::finish(&mut session, FocusState::Stopped, now);
+                    Ok("stopped")
+                }
+            };
+            save_focus_progress(pool, &session, &completed_work).await?;
+            println!("Focus session {}", message?);
+        }
+        FocusCommands::Status => {
+            match get_last_focus_session(pool).await? {
+                Some(mut session) => {
+                    // Show where the session is now, even if the service has not caught up yet
+                    focus::advance(&mut session, now);
+                    display_focus_session(&session, focus::remaining(&session, now));
+                }
+                None => println!("No focus session yet"),
+            }
+            let today = chrono::Local::now().date_naive();
+            let totals = get_focus_totals(pool, today).await?;
+            let (cycles, work_secs) = totals
+                .first()
+                .map_or((0, 0), |total| (total.cycles, total.work_secs));
+            println!(
+                "{:<16} {} cycle(s), {} focused",
+                "Today:",
+                cycles,
+                format_duration(work_secs)
+            );
+        }
+        FocusCommands::Report { days } => {
+            let since = chrono::Local::now().date_naive() - chrono::Duration::days(days.max(1) - 1);
+            display_focus_totals(&get_focus_totals(pool, since).await?);
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_trash_command(
+    action: TrashCommands,
+    pool: &sqlx::SqlitePool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        TrashCommands::List => {
+            let trashed = list_trash(pool).await?;
+            if trashed.is_empty() {
+                println!("The trash is empty");
+            } else {
+                display_trash(&trashed, config::get().trash_days);
+            }
+        }
+        TrashCommands::Purge { name } => match purge_poke(pool, &name).await {
+            Ok(()) => println!("Job '{}' permanently deleted", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        TrashCommands::Empty => {
+            let purged = purge_trash(pool, None).await?;
+            println!("Permanently deleted {} job(s)", purged);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_calendar_command(
+    action: CalendarCommands,
+    pool: &sqlx::SqlitePool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        CalendarCommands::Create { name } => match create_calendar(pool, &name).await {
+            Ok(()) => println!("Calendar '{}' created successfully", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        CalendarCommands::Delete { name } => match delete_calendar(pool, &name).await {
+            Ok(()) => println!("Calendar '{}' deleted successfully", name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        CalendarCommands::List => match list_calendars(pool).await {
+            Ok(calendars) => {
+                if calendars.is_empty() {
+                    println!("No calendars created yet");
+                } else {
+                    display_calendars(&calendars);
+                }
+            }
+            Err(err) => println!("ERROR: {}", err),
+        },
+        CalendarCommands::Show { name } => match get_calendar_by_name(pool, &name).await {
+            Ok(calendar) => {
+                let entries = list_calendar_entries(pool, calendar.id).await?;
+                display_calendar_detail(&calendar, &entries);
+            }
+            Err(err) => println!("ERROR: {}", err),
+        },
+        CalendarCommands::AddDate {
+            name,
+            date,
+            until,
+            summary,
+        } => {
+            let result: Result<(), Box<dyn std::error::Error>> = async {
+                let start = parse_date(&date)?;
+                let end = until
+                    .as_deref()
+                    .map(parse_date)
+                    .transpose()?
+                    .unwrap_or(start);
+                add_calendar_entry(pool, &name, start, end, summary.as_deref()).await
+            }
+            .await;
+            match result {
+                Ok(()) => println!("Dates added to calendar '{}'", name),
+                Err(err) => println!("ERROR: {}", err),
+            }
+        }
+        CalendarCommands::RemoveDate { name, date } => {
+            let result: Result<u64, Box<dyn std::error::Error>> = async {
+                let start = parse_date(&date)?;
+                remove_calendar_entry(pool, &name, start).await
+            }
+            .await;
+            match result {
+                Ok(removed) => println!("Removed {} entry(ies) from calendar '{}'", removed, name),
+                Err(err) => println!("ERROR: {}", err),
+            }
+        }
+        CalendarCommands::Import { name, file } => {
+            let result: Result<usize, Box<dyn std::error::Error>> = async {
+                let content = std::fs::read_to_string(&file)
+                    .map_err(|err| format!("Failed to read {}: {}", file, err))?;
+                let events = parse_ics_events(&content)?;
+                if get_calendar_by_name(pool, &name).await.is_err() {
+                    create_calendar(pool, &name).await?;
+                }
+                for event in &events {
+                    add_calendar_entry(
+                        pool,
+                        &name,
+                        event.start,
+                        event.end,
+                        event.summary.as_deref(),
+                    )
+                    .await?;
+                }
+                Ok(events.len())
+            }
+            .await;
+            match result {
+                Ok(count) => println!("Imported {} event(s) into calendar '{}'", count, name),
+                Err(err) => println!("ERROR: {}", err),
+            }
+        }
+        CalendarCommands::Assign { name, job } => match assign_calendar(pool, &name, &job).await {
+            Ok(()) => println!("Job '{}' now skips dates in calendar '{}'", job, name),
+            Err(err) => println!("ERROR: {}", err),
+        },
+        CalendarCommands::Unassign { name, job } => {
+            match unassign_calendar(pool, &name, &job).await {
+                Ok(()) => println!("Job '{}' no longer skips dates in calendar '{}'", job, name),
+                Err(err) => println!("ERROR: {}", err),
+            }
         }
     }
 
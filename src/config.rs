@@ -0,0 +1,327 @@
+use crate::database::{config_dir, data_dir, is_development_mode};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// System-wide configuration file, read before the user's
+pub const SYSTEM_CONFIG: &str = "/etc/poke_me/config.toml";
+
+/// Prefix of the environment variables overriding configuration keys, e.g.
+/// `POKE_ME_APP_NAME`
+const ENV_PREFIX: &str = "POKE_ME_";
+
+/// Every configuration key, in the order `config show` lists them
+pub const KEYS: &[&str] = &[
+    "db",
+    "pid_file",
+    "control_file",
+    "refresh_file",
+    "app_name",
+    "default_icon",
+    "default_sound",
+    "api_port",
+    "backup_dir",
+    "backup_keep",
+    "trash_days",
+];
+
+/// Configuration loaded once at start-up
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Table of per-profile settings in configuration files, e.g. `[profiles.work]`
+pub const PROFILES_TABLE: &str = "profiles";
+
+/// Settings given on the command line
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Profile selected with `--profile`
+    pub profile: Option<String>,
+    /// Database given with `--db`
+    pub db: Option<String>,
+    /// `KEY=VALUE` pairs given with `--option`
+    pub options: Vec<String>,
+}
+
+/// Settings that used to be hard-coded, merged from (lowest first) built-in
+/// defaults, `/etc/poke_me/config.toml`, the user's `config.toml`,
+/// `POKE_ME_*` environment variables and `--option`/`--db` flags
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Named profile, with its own database and service instance
+    pub profile: Option<String>,
+    /// SQLite database file
+    pub db: PathBuf,
+    /// File holding the PID of the running service
+    pub pid_file: PathBuf,
+    /// File whose removal asks the service to stop
+    pub control_file: PathBuf,
+    /// File whose creation asks the service to reload all jobs
+    pub refresh_file: PathBuf,
+    /// Application name shown on notifications
+    pub app_name: String,
+    /// Icon of notifications without one of their own
+    pub default_icon: String,
+    /// Sound of notifications with sound on but no sound of their own
+    pub default_sound: String,
+    /// Port of the HTTP API when `--api-port` is not given
+    pub api_port: u16,
+    /// Directory of the daily backups made by the service
+    pub backup_dir: PathBuf,
+    /// Number of daily backups kept, 0 to make none
+    pub backup_keep: usize,
+    /// Days after which the service purges trashed jobs, 0 to keep them
+    pub trash_days: u32,
+    /// Where each key not left at its default was set
+    sources: HashMap<&'static str, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::for_profile(None)
+    }
+}
+
+/// Check that a profile name can be used in file and bus names
+pub fn validate_profile(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "Invalid profile name '{}': use letters, digits, '_' and '-', starting with a letter",
+            name
+        ));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Built-in defaults. A profile gets its own database and service files
+    fn for_profile(profile: Option<&str>) -> Self {
+        let suffix = profile.map_or(String::new(), |profile| format!(".{}", profile));
+        let db_name = format!("poke{}.db", suffix);
+        let (db, backup_dir) = if is_development_mode() {
+            (PathBuf::from(db_name), PathBuf::from("backups"))
+        } else {
+            (data_dir().join(db_name), data_dir().join("backups"))
+        };
+        Config {
+            profile: profile.map(str::to_string),
+            db,
+            pid_file: PathBuf::from(format!("/tmp/poke_me{}.pid", suffix)),
+            control_file: PathBuf::from(format!("/tmp/poke_me{}.control", suffix)),
+            refresh_file: PathBuf::from(format!("/tmp/poke_me{}.refresh", suffix)),
+            app_name: "Poke Me".to_string(),
+            default_icon: "clock".to_string(),
+            default_sound: "message-new-instant".to_string(),
+            api_port: 7878,
+            backup_dir,
+            backup_keep: 7,
+            trash_days: 30,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Set a key from its text form, checking the value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "db" => self.db = PathBuf::from(non_empty(value)?),
+            "pid_file" => self.pid_file = PathBuf::from(non_empty(value)?),
+            "control_file" => self.control_file = PathBuf::from(non_empty(value)?),
+            "refresh_file" => self.refresh_file = PathBuf::from(non_empty(value)?),
+            "app_name" => self.app_name = non_empty(value)?,
+            "default_icon" => self.default_icon = non_empty(value)?,
+            "default_sound" => self.default_sound = non_empty(value)?,
+            "api_port" => {
+                self.api_port = match value.trim().parse::<u16>() {
+                    Ok(port) if port > 0 => port,
+                    _ => return Err(format!("expected a port number, got '{}'", value)),
+                }
+            }
+            "backup_dir" => self.backup_dir = PathBuf::from(non_empty(value)?),
+            "backup_keep" => {
+                self.backup_keep = value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("expected a number of backups, got '{}'", value))?
+            }
+            "trash_days" => {
+                self.trash_days = value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("expected a number of days, got '{}'", value))?
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// The text form of a key's value
+    pub fn get(&self, key: &str) -> Result<String, String> {
+        let value = match key {
+            "db" => self.db.display().to_string(),
+            "pid_file" => self.pid_file.display().to_string(),
+            "control_file" => self.control_file.display().to_string(),
+            "refresh_file" => self.refresh_file.display().to_string(),
+            "app_name" => self.app_name.clone(),
+            "default_icon" => self.default_icon.clone(),
+            "default_sound" => self.default_sound.clone(),
+            "api_port" => self.api_port.to_string(),
+            "backup_dir" => self.backup_dir.display().to_string(),
+            "backup_keep" => self.backup_keep.to_string(),
+            "trash_days" => self.trash_days.to_string(),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    /// Where a key's value comes from
+    pub fn source(&self, key: &str) -> &str {
+        self.sources.get(key).map_or("default", String::as_str)
+    }
+
+    /// Set a key and remember where the value came from. Errors name the key
+    /// and the source
+    fn set_from(&mut self, key: &str, value: &str, source: &str) -> Result<(), String> {
+        let key = KEYS
+            .iter()
+            .copied()
+            .find(|known| *known == key)
+            .ok_or_else(|| format!("{}: {}", source, unknown_key(key)))?;
+        self.set(key, value)
+            .map_err(|err| format!("{}: invalid value for '{}': {}", source, key, err))?;
+        self.sources.insert(key, source.to_string());
+        Ok(())
+    }
+
+    /// Apply the keys of a configuration file, if it exists, then those of
+    /// the selected profile's table
+    fn merge_file(&mut self, path: &Path) -> Result<(), String> {
+        let source = path.display().to_string();
+        let mut table = match read_table(path)? {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let profiles = table.remove(PROFILES_TABLE);
+        self.merge_table(&table, &source)?;
+
+        let profiles = match profiles {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(other) => {
+                return Err(format!(
+                    "{}: invalid value for '{}': expected a table, got {}",
+                    source,
+                    PROFILES_TABLE,
+                    other.type_str()
+                ))
+            }
+            None => return Ok(()),
+        };
+        let Some(profile) = self.profile.clone() else {
+            return Ok(());
+        };
+        match profiles.get(&profile) {
+            Some(toml::Value::Table(table)) => {
+                let source = format!("{} [{}.{}]", source, PROFILES_TABLE, profile);
+                self.merge_table(table, &source)
+            }
+            Some(other) => Err(format!(
+                "{}: invalid value for '{}.{}': expected a table, got {}",
+                source,
+                PROFILES_TABLE,
+                profile,
+                other.type_str()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn merge_table(&mut self, table: &toml::Table, source: &str) -> Result<(), String> {
+        for (key, value) in table {
+            let text = match value {
+                toml::Value::String(text) => text.clone(),
+                toml::Value::Integer(number) => number.to_string(),
+                other => {
+                    return Err(format!(
+                        "{}: invalid value for '{}': expected a string or number, got {}",
+                        source,
+                        key,
+                        other.type_str()
+                    ))
+                }
+            };
+            self.set_from(key, &text, source)?;
+        }
+        Ok(())
+    }
+
+    /// Load every layer, the command line coming last
+    pub fn load(overrides: &Overrides) -> Result<Config, String> {
+        if let Some(profile) = &overrides.profile {
+            validate_profile(profile)?;
+        }
+        let mut config = Config::for_profile(overrides.profile.as_deref());
+        config.merge_file(Path::new(SYSTEM_CONFIG))?;
+        config.merge_file(&user_config_path())?;
+        for key in KEYS {
+            let var = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                config.set_from(key, &value, &var)?;
+            }
+        }
+        for entry in &overrides.options {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("--option {}: expected KEY=VALUE", entry))?;
+            config.set_from(key.trim(), value, "--option")?;
+        }
+        if let Some(db) = &overrides.db {
+            config.set_from("db", db, "--db")?;
+        }
+        Ok(config)
+    }
+}
+
+fn non_empty(value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "unknown key '{}', expected one of: {}",
+        key,
+        KEYS.join(", ")
+    )
+}
+
+/// The user's configuration file
+pub fn user_config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Read a configuration file as a TOML table, `None` if it does not exist
+pub fn read_table(path: &Path) -> Result<Option<toml::Table>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+    text.parse::<toml::Table>()
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path.display(), err.message()))
+}
+
+/// Load the configuration for the rest of the run
+pub fn init(overrides: &Overrides) -> Result<(), String> {
+    let config = Config::load(overrides)?;
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The configuration loaded by `init`, or the defaults if it was not called
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,365 @@
+//! Validation and normalisation of cron expressions.
+//!
+//! Users may write standard 5-field crontab lines, the scheduler's 6-field
+//! form (with seconds), a 7-field form with a trailing year, or one of the
+//! `@` macros. Everything is normalised to the 6-field form, keeping the year
+//! as a 7th field only when it actually restricts the schedule.
+
+/// Lowest and highest year accepted in the year field
+const YEAR_RANGE: (i64, i64) = (1970, 2099);
+
+pub const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+pub const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Second,
+    Minute,
+    Hour,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+    Year,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Second => "second",
+            Field::Minute => "minute",
+            Field::Hour => "hour",
+            Field::DayOfMonth => "day-of-month",
+            Field::Month => "month",
+            Field::DayOfWeek => "day-of-week",
+            Field::Year => "year",
+        }
+    }
+
+    fn range(self) -> (i64, i64) {
+        match self {
+            Field::Second | Field::Minute => (0, 59),
+            Field::Hour => (0, 23),
+            Field::DayOfMonth => (1, 31),
+            Field::Month => (1, 12),
+            Field::DayOfWeek => (0, 7),
+            Field::Year => YEAR_RANGE,
+        }
+    }
+
+    /// Parse a single value, accepting month and weekday names
+    fn value(self, value: &str) -> Result<i64, String> {
+        let upper = value.to_uppercase();
+        let named = match self {
+            Field::Month => MONTH_NAMES
+                .iter()
+                .position(|name| *name == upper)
+                .map(|i| i as i64 + 1),
+            Field::DayOfWeek => DAY_NAMES
+                .iter()
+                .position(|name| *name == upper)
+                .map(|i| i as i64),
+            _ => None,
+        };
+        let number = match named {
+            Some(number) => number,
+            None => value
+                .parse::<i64>()
+                .map_err(|_| format!("{} field: '{}' is not a number", self.name(), value))?,
+        };
+        let (min, max) = self.range();
+        if number < min || number > max {
+            return Err(format!(
+                "{} field: '{}' is out of range {}-{}",
+                self.name(),
+                value,
+                min,
+                max
+            ));
+        }
+        Ok(number)
+    }
+}
+
+/// Validate one comma-separated item of a field
+fn validate_item(field: Field, item: &str) -> Result<(), String> {
+    if item.is_empty() {
+        return Err(format!("{} field: empty list item", field.name()));
+    }
+
+    let (base, step) = match item.split_once('/') {
+        Some((base, step)) => (base, Some(step)),
+        None => (item, None),
+    };
+    if let Some(step) = step {
+        let (_, max) = field.range();
+        match step.parse::<i64>() {
+            Ok(step) if step >= 1 && step <= max.max(1) => {}
+            _ => {
+                return Err(format!(
+                    "{} field: step '{}' must be a number between 1 and {}",
+                    field.name(),
+                    step,
+                    max
+                ))
+            }
+        }
+    }
+
+    let upper = base.to_uppercase();
+    match (field, upper.as_str()) {
+        (_, "*") => return Ok(()),
+        (Field::DayOfMonth | Field::DayOfWeek, "?" | "L") if step.is_none() => return Ok(()),
+        (Field::DayOfMonth, "LW") => {
+            return Err(format!("{} field: 'LW' is not supported", field.name()))
+        }
+        _ => {}
+    }
+
+    // Special day-of-month and day-of-week forms: 15W, 5L, 5#3
+    if step.is_none() {
+        if field == Field::DayOfMonth {
+            if let Some(day) = upper.strip_suffix('W') {
+                return field.value(day).map(|_| ());
+            }
+        }
+        if field == Field::DayOfWeek {
+            if let Some(day) = upper.strip_suffix('L') {
+                return field.value(day).map(|_| ());
+            }
+            if let Some((day, nth)) = upper.split_once('#') {
+                field.value(day)?;
+                return match nth.parse::<i64>() {
+                    Ok(1..=5) => Ok(()),
+                    _ => Err(format!(
+                        "{} field: '#{}' must be between #1 and #5",
+                        field.name(),
+                        nth
+                    )),
+                };
+            }
+        }
+    }
+
+    match base.split_once('-') {
+        Some((start, end)) => {
+            let (start_value, end_value) = (field.value(start)?, field.value(end)?);
+            if start_value > end_value {
+                return Err(format!(
+                    "{} field: range '{}' starts after it ends",
+                    field.name(),
+                    base
+                ));
+            }
+            Ok(())
+        }
+        None => field.value(base).map(|_| ()),
+    }
+}
+
+fn validate_field(field: Field, value: &str) -> Result<(), String> {
+    value
+        .split(',')
+        .try_for_each(|item| validate_item(field, item))
+}
+
+/// Expand an `@` macro into its 6-field form
+fn expand_macro(expr: &str) -> Result<&'static str, String> {
+    match expr.to_lowercase().as_str() {
+        "@yearly" | "@annually" => Ok("0 0 0 1 1 *"),
+        "@monthly" => Ok("0 0 0 1 * *"),
+        "@weekly" => Ok("0 0 0 * * 0"),
+        "@daily" | "@midnight" => Ok("0 0 0 * * *"),
+        "@hourly" => Ok("0 0 * * * *"),
+        "@reboot" => Err("@reboot is not supported, use the service's startup instead".into()),
+        _ => Err(format!(
+            "unknown macro '{}'. Expected one of @yearly, @annually, @monthly, @weekly, @daily, @midnight, @hourly",
+            expr
+        )),
+    }
+}
+
+/// Validate a cron expression and bring it to its canonical stored form
+pub fn normalize_cron(expr: &str) -> Result<String, String> {
+    let trimmed = expr.trim();
+    let expanded = if trimmed.starts_with('@') {
+        expand_macro(trimmed)
+            .map_err(|err| format!("Invalid cron expression '{}': {}", expr, err))?
+    } else {
+        trimmed
+    };
+
+    let mut fields: Vec<&str> = expanded.split_whitespace().collect();
+    let layout: &[Field] = match fields.len() {
+        5 => &[
+            Field::Minute,
+            Field::Hour,
+            Field::DayOfMonth,
+            Field::Month,
+            Field::DayOfWeek,
+        ],
+        6 => &[
+            Field::Second,
+            Field::Minute,
+            Field::Hour,
+            Field::DayOfMonth,
+            Field::Month,
+            Field::DayOfWeek,
+        ],
+        7 => &[
+            Field::Second,
+            Field::Minute,
+            Field::Hour,
+            Field::DayOfMonth,
+            Field::Month,
+            Field::DayOfWeek,
+            Field::Year,
+        ],
+        count => {
+            return Err(format!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day month weekday), \
+                 6 fields (with leading second), 7 fields (with trailing year) or an @macro, got {}",
+                expr, count
+            ))
+        }
+    };
+
+    for (field, value) in layout.iter().zip(&fields) {
+        validate_field(*field, value)
+            .map_err(|err| format!("Invalid cron expression '{}': {}", expr, err))?;
+    }
+
+    if fields.len() == 5 {
+        fields.insert(0, "0");
+    }
+    if fields.len() == 7 && fields[6] == "*" {
+        fields.pop();
+    }
+    Ok(fields.join(" "))
+}
+
+/// The part of a canonical expression understood by the scheduler (no year)
+pub fn scheduler_cron(cron: &str) -> String {
+    cron.split_whitespace()
+        .take(6)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Year restriction of a canonical 7-field expression
+#[derive(Debug, Clone)]
+pub struct YearField {
+    ranges: Vec<(i32, i32, i32)>,
+}
+
+impl YearField {
+    /// Extract the year field, if the expression has one
+    pub fn from_cron(cron: &str) -> Option<YearField> {
+        let field = cron.split_whitespace().nth(6)?;
+        let ranges = field
+            .split(',')
+            .filter_map(|item| {
+                let (base, step) = match item.split_once('/') {
+                    Some((base, step)) => (base, step.parse().ok()?),
+                    None => (item, 1),
+                };
+                let (start, end) = match base.split_once('-') {
+                    Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+                    None if base == "*" => (YEAR_RANGE.0 as i32, YEAR_RANGE.1 as i32),
+                    None if item.contains('/') => (base.parse().ok()?, YEAR_RANGE.1 as i32),
+                    None => {
+                        let year = base.parse().ok()?;
+                        (year, year)
+                    }
+                };
+                Some((start, end, step))
+            })
+            .collect();
+        Some(YearField { ranges })
+    }
+
+    pub fn matches(&self, year: i32) -> bool {
+        self.ranges
+            .iter()
+            .any(|(start, end, step)| year >= *start && year <= *end && (year - start) % step == 0)
+    }
+
+    /// Last year the expression can fire in
+    pub fn last_year(&self) -> i32 {
+        self.ranges
+            .iter()
+            .map(|(_, end, _)| *end)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn five_fields_get_a_zero_second() {
+        assert_eq!(normalize_cron("30 9 * * 1-5").unwrap(), "0 30 9 * * 1-5");
+        assert_eq!(
+            normalize_cron("  */15 * * * *  ").unwrap(),
+            "0 */15 * * * *"
+        );
+        assert_eq!(normalize_cron("0 8 1 JAN mon").unwrap(), "0 0 8 1 JAN mon");
+    }
+
+    #[test]
+    fn six_fields_are_kept() {
+        assert_eq!(normalize_cron("15 0 12 * * *").unwrap(), "15 0 12 * * *");
+        assert_eq!(normalize_cron("0 0 9 L * ?").unwrap(), "0 0 9 L * ?");
+        assert_eq!(normalize_cron("0 0 9 ? * 5#3").unwrap(), "0 0 9 ? * 5#3");
+    }
+
+    #[test]
+    fn seven_fields_keep_a_restricting_year() {
+        assert_eq!(
+            normalize_cron("0 0 9 1 1 * 2030").unwrap(),
+            "0 0 9 1 1 * 2030"
+        );
+        assert_eq!(normalize_cron("0 0 9 1 1 * *").unwrap(), "0 0 9 1 1 *");
+        assert_eq!(scheduler_cron("0 0 9 1 1 * 2030"), "0 0 9 1 1 *");
+    }
+
+    #[test]
+    fn macros_expand() {
+        assert_eq!(normalize_cron("@daily").unwrap(), "0 0 0 * * *");
+        assert_eq!(normalize_cron("@Weekly").unwrap(), "0 0 0 * * 0");
+        assert!(normalize_cron("@reboot").is_err());
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expr in [
+            "* * * *",
+            "0 0 0 0 * * * *",
+            "60 * * * *",
+            "0 24 * * *",
+            "0 0 32 * *",
+            "0 0 * 13 *",
+            "0 0 * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 9 * * 5#6",
+            "0 0 9 1 1 * 1969",
+            "0 0 9 LW * *",
+        ] {
+            assert!(normalize_cron(expr).is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn year_field_matches() {
+        let years = YearField::from_cron("0 0 9 1 1 * 2025,2030-2040/5").unwrap();
+        assert!(years.matches(2025));
+        assert!(years.matches(2035));
+        assert!(!years.matches(2031));
+        assert_eq!(years.last_year(), 2040);
+        assert!(YearField::from_cron("0 0 9 1 1 *").is_none());
+    }
+}
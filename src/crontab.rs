@@ -0,0 +1,533 @@
+use crate::cron_expr::normalize_cron;
+use crate::database::Urgency;
+use crate::ics::cron_to_rrule;
+use chrono::{Datelike, Local, NaiveDateTime, TimeZone, Timelike, Utc};
+use std::process::Command;
+
+/// A `notify-send` invocation found in a crontab line or `at` job
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedEntry {
+    /// Where the entry came from, e.g. "line 4" or "at job 12"
+    pub source: String,
+    /// RRULE for crontab lines, or a one-off 7-field cron expression in UTC
+    /// for `at` jobs
+    pub schedule: String,
+    pub summary: String,
+    pub body: Option<String>,
+    pub urgency: Urgency,
+    pub icon: Option<String>,
+    /// Expiry from `--expire-time`, in seconds with 0 for never
+    pub timeout_secs: Option<i64>,
+    pub category: Option<String>,
+    /// Whether the entry fires only once (`at` jobs)
+    pub once: bool,
+}
+
+/// A line that could not be turned into a job, with the reason
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedEntry {
+    pub source: String,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Parse crontab content, keeping the lines that run `notify-send`
+pub fn parse_crontab(content: &str) -> (Vec<ImportedEntry>, Vec<SkippedEntry>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') || is_variable_assignment(line) {
+            continue;
+        }
+        let source = format!("line {}", index + 1);
+        let skip = |reason: String| SkippedEntry {
+            source: source.clone(),
+            text: line.to_string(),
+            reason,
+        };
+
+        let Some((schedule, command)) = split_schedule(line) else {
+            skipped.push(skip("not a crontab entry".to_string()));
+            continue;
+        };
+        let result = parse_notify_send(&crontab_command(command))
+            .and_then(|notification| Ok((notification, local_schedule(&schedule)?)));
+        match result {
+            Ok((notification, schedule)) => entries.push(ImportedEntry {
+                source,
+                schedule,
+                summary: notification.summary,
+                body: notification.body,
+                urgency: notification.urgency,
+                icon: notification.icon,
+                timeout_secs: notification.timeout_secs,
+                category: notification.category,
+                once: false,
+            }),
+            Err(reason) => skipped.push(skip(reason)),
+        }
+    }
+
+    (entries, skipped)
+}
+
+/// Read the current user's crontab
+pub fn read_user_crontab() -> Result<String, String> {
+    let output = Command::new("crontab")
+        .arg("-l")
+        .output()
+        .map_err(|err| format!("Failed to run 'crontab -l': {}", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // A user without a crontab simply has nothing to import
+        if stderr.contains("no crontab") {
+            return Ok(String::new());
+        }
+        return Err(format!("'crontab -l' failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Read the pending `at` jobs of the current user, keeping those that run `notify-send`
+pub fn read_at_jobs() -> Result<(Vec<ImportedEntry>, Vec<SkippedEntry>), String> {
+    let queue = run_at_command("atq", &[])?;
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+
+    for line in queue.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((id, time)) = parse_atq_line(line) else {
+            continue;
+        };
+        let source = format!("at job {}", id);
+        let script = run_at_command("at", &["-c", id])?;
+
+        // The job's own commands come after the environment set up by `at`
+        let result = script
+            .lines()
+            .rev()
+            .find_map(|line| parse_notify_send(line).ok())
+            .ok_or_else(|| "no notify-send command".to_string());
+        match result {
+            Ok(notification) => entries.push(ImportedEntry {
+                source,
+                schedule: one_off_cron(time),
+                summary: notification.summary,
+                body: notification.body,
+                urgency: notification.urgency,
+                icon: notification.icon,
+                timeout_secs: notification.timeout_secs,
+                category: notification.category,
+                once: true,
+            }),
+            Err(reason) => skipped.push(SkippedEntry {
+                source,
+                text: line.to_string(),
+                reason,
+            }),
+        }
+    }
+
+    Ok((entries, skipped))
+}
+
+fn run_at_command(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'{}' failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse an `atq` line such as "12\tSat Oct 19 10:00:00 2026 a user"
+fn parse_atq_line(line: &str) -> Option<(&str, chrono::DateTime<Utc>)> {
+    let mut parts = line.split_whitespace();
+    let id = parts.next()?;
+    let time = parts.by_ref().take(5).collect::<Vec<_>>().join(" ");
+    let naive = NaiveDateTime::parse_from_str(&time, "%a %b %e %H:%M:%S %Y").ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some((id, local.with_timezone(&Utc)))
+}
+
+/// A cron expression matching exactly one moment, using the year field
+fn one_off_cron(time: chrono::DateTime<Utc>) -> String {
+    format!(
+        "{} {} {} {} {} * {}",
+        time.second(),
+        time.minute(),
+        time.hour(),
+        time.day(),
+        time.month(),
+        time.year()
+    )
+}
+
+/// Convert a crontab schedule into an RRULE. cron runs crontabs in local
+/// time, like RRULEs, while cron expressions of jobs run in UTC
+fn local_schedule(schedule: &str) -> Result<String, String> {
+    let cron = normalize_cron(schedule)?;
+    // cron fires when either day field matches if both are restricted, an
+    // RRULE only when both do. Fields starting with '*' do not count
+    let fields: Vec<&str> = cron.split_whitespace().collect();
+    let restricted = |field: &str| !field.starts_with('*') && field != "?";
+    if restricted(fields[3]) && restricted(fields[5]) {
+        return Err(format!(
+            "schedule '{}' fires on day of month {} or on weekday {}, which one job cannot \
+             express; split it into two lines, one for each day field",
+            schedule, fields[3], fields[5]
+        ));
+    }
+    cron_to_rrule(&cron)
+        .ok_or_else(|| format!("schedule '{}' has no local-time equivalent", schedule))
+}
+
+/// Environment lines such as `PATH=/usr/bin` or `MAILTO=""`
+fn is_variable_assignment(line: &str) -> bool {
+    match line.split_once('=') {
+        Some((name, _)) => {
+            let name = name.trim();
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Split a crontab line into its schedule and command
+fn split_schedule(line: &str) -> Option<(String, &str)> {
+    let mut rest = line;
+    let mut fields = Vec::new();
+    let count = if line.starts_with('@') { 1 } else { 5 };
+    for _ in 0..count {
+        let trimmed = rest.trim_start();
+        let end = trimmed.find(char::is_whitespace)?;
+        fields.push(&trimmed[..end]);
+        rest = &trimmed[end..];
+    }
+    let command = rest.trim();
+    if command.is_empty() {
+        return None;
+    }
+    Some((fields.join(" "), command))
+}
+
+/// Apply crontab's `%` handling: the first unescaped `%` ends the command
+/// (the rest is standard input) and `\%` is a literal percent sign
+fn crontab_command(command: &str) -> String {
+    let mut result = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'%') => {
+                result.push('%');
+                chars.next();
+            }
+            '%' => break,
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// The notification described by a `notify-send` call
+struct NotifySend {
+    summary: String,
+    body: Option<String>,
+    urgency: Urgency,
+    icon: Option<String>,
+    timeout_secs: Option<i64>,
+    category: Option<String>,
+}
+
+/// Find the first `notify-send` call in a shell command and read its arguments
+fn parse_notify_send(command: &str) -> Result<NotifySend, String> {
+    let segments = shell_segments(command)?;
+    let args = segments
+        .iter()
+        .find_map(|words| {
+            // Skip leading `VAR=value` assignments such as DISPLAY=:0
+            let mut words = words.iter().skip_while(|word| is_variable_assignment(word));
+            let program = words.next()?;
+            let program = program.rsplit('/').next().unwrap_or(program);
+            (program == "notify-send").then(|| words.cloned().collect::<Vec<_>>())
+        })
+        .ok_or_else(|| "no notify-send command".to_string())?;
+
+    let mut urgency = Urgency::default();
+    let mut icon = None;
+    let mut timeout_secs = None;
+    let mut category = None;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    let mut options_done = false;
+    while let Some(arg) = args.next() {
+        if options_done || !arg.starts_with('-') || arg == "-" {
+            positional.push(arg);
+            continue;
+        }
+        if arg == "--" {
+            options_done = true;
+            continue;
+        }
+
+        // Split "--urgency=critical", "-ucritical" or "-u critical"
+        let (option, inline_value) = match arg.strip_prefix("--") {
+            Some(long) => match long.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (long.to_string(), None),
+            },
+            None => {
+                let mut chars = arg[1..].chars();
+                let flag = chars
+                    .next()
+                    .filter(char::is_ascii_alphanumeric)
+                    .ok_or_else(|| format!("unknown notify-send option '{}'", arg))?;
+                let value = chars.as_str();
+                (
+                    flag.to_string(),
+                    (!value.is_empty()).then(|| value.to_string()),
+                )
+            }
+        };
+        let takes_value = matches!(
+            option.as_str(),
+            "u" | "urgency"
+                | "t"
+                | "expire-time"
+                | "a"
+                | "app-name"
+                | "i"
+                | "icon"
+                | "c"
+                | "category"
+                | "h"
+                | "hint"
+                | "r"
+                | "replace-id"
+                | "A"
+                | "action"
+        );
+        let value = if takes_value {
+            match inline_value {
+                Some(value) => Some(value),
+                None => Some(
+                    args.next()
+                        .ok_or_else(|| format!("notify-send option '{}' needs a value", arg))?,
+                ),
+            }
+        } else {
+            None
+        };
+
+        match (option.as_str(), value) {
+            ("u" | "urgency", Some(value)) => urgency = value.parse()?,
+            ("i" | "icon", Some(value)) => icon = Some(value),
+            ("c" | "category", Some(value)) => category = Some(value),
+            ("t" | "expire-time", Some(value)) => {
+                let millis: i64 = value
+                    .parse()
+                    .map_err(|_| format!("invalid expire time '{}'", value))?;
+                // notify-send counts milliseconds, -1 is the server default
+                timeout_secs = match millis {
+                    0 => Some(0),
+                    millis if millis > 0 => Some((millis + 999) / 1000),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let summary = positional
+        .next()
+        .filter(|summary| !summary.trim().is_empty())
+        .ok_or_else(|| "notify-send has no summary".to_string())?;
+    let body = positional.next().filter(|body| !body.is_empty());
+    Ok(NotifySend {
+        summary,
+        body,
+        urgency,
+        icon,
+        timeout_secs,
+        category,
+    })
+}
+
+/// Split a shell command into simple commands at `;`, `&&`, `||`, `|` and `&`,
+/// resolving quotes and backslash escapes
+fn shell_segments(command: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut segments = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            ';' | '&' | '|' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if matches!(chars.peek(), Some('&' | '|')) {
+                    chars.next();
+                }
+                segments.push(std::mem::take(&mut words));
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    segments.push(words);
+
+    Ok(segments
+        .into_iter()
+        .filter(|words| !words.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imported(line: &str) -> ImportedEntry {
+        let (entries, skipped) = parse_crontab(line);
+        assert!(skipped.is_empty(), "{:?}", skipped);
+        entries.into_iter().next().unwrap()
+    }
+
+    fn skip_reason(line: &str) -> String {
+        let (entries, skipped) = parse_crontab(line);
+        assert!(entries.is_empty(), "{:?}", entries);
+        skipped.into_iter().next().unwrap().reason
+    }
+
+    #[test]
+    fn quoted_arguments() {
+        let entry = imported(
+            r#"30 9 * * 1-5 DISPLAY=:0 /usr/bin/notify-send -u critical "Stand up" 'Team call, room "B"'"#,
+        );
+        assert_eq!(entry.summary, "Stand up");
+        assert_eq!(entry.body.as_deref(), Some(r#"Team call, room "B""#));
+        assert_eq!(entry.urgency, Urgency::Critical);
+        assert_eq!(entry.source, "line 1");
+    }
+
+    #[test]
+    fn escaped_characters() {
+        let entry = imported(r#"0 12 * * * notify-send Lunch\ time "Eat \"now\"" && echo done"#);
+        assert_eq!(entry.summary, "Lunch time");
+        assert_eq!(entry.body.as_deref(), Some(r#"Eat "now""#));
+
+        // A bare % ends the command, \% is a literal percent sign
+        let entry = imported(r"0 18 * * * notify-send 'Battery' '100\% charged'%ignored");
+        assert_eq!(entry.body.as_deref(), Some("100% charged"));
+    }
+
+    #[test]
+    fn options_with_values() {
+        let entry = imported("@daily notify-send -ucritical --icon=clock -t 1500 -c email Mail");
+        assert_eq!(entry.urgency, Urgency::Critical);
+        assert_eq!(entry.icon.as_deref(), Some("clock"));
+        assert_eq!(entry.timeout_secs, Some(2));
+        assert_eq!(entry.category.as_deref(), Some("email"));
+        assert_eq!(entry.summary, "Mail");
+    }
+
+    #[test]
+    fn schedules_stay_in_local_time() {
+        assert_eq!(
+            imported("30 9 * * mon-fri notify-send Standup").schedule,
+            "FREQ=DAILY;BYHOUR=9;BYMINUTE=30;BYSECOND=0;BYDAY=MO,TU,WE,TH,FR"
+        );
+        assert_eq!(
+            imported("@daily notify-send Backup").schedule,
+            "FREQ=DAILY;BYHOUR=0;BYMINUTE=0;BYSECOND=0"
+        );
+        assert!(skip_reason("0 9 L * * notify-send Rent").contains("local-time"));
+    }
+
+    #[test]
+    fn both_day_fields_are_rejected() {
+        // cron reads these as "the 1st or any Monday", an RRULE as "both"
+        assert!(skip_reason("0 9 1 * MON notify-send Review").contains("split it"));
+        assert!(skip_reason("0 9 1-7 * 5 notify-send Pay").contains("split it"));
+        // A starred field leaves the other one in charge, as in cron
+        assert_eq!(
+            imported("0 9 */2 * MON notify-send Gym").schedule,
+            "FREQ=DAILY;BYHOUR=9;BYMINUTE=0;BYSECOND=0;BYMONTHDAY=1,3,5,7,9,11,13,15,17,19,21,\
+             23,25,27,29,31;BYDAY=MO"
+        );
+    }
+
+    #[test]
+    fn unparseable_lines_are_skipped() {
+        assert!(skip_reason("0 9 * * * notify-send -é x").contains("-é"));
+        assert!(skip_reason("0 9 * * * notify-send 'Unclosed").contains("quote"));
+        assert_eq!(
+            skip_reason("0 9 * * * echo hello"),
+            "no notify-send command"
+        );
+        assert_eq!(skip_reason("0 9 * *"), "not a crontab entry");
+    }
+
+    #[test]
+    fn comments_and_variables_are_ignored() {
+        let (entries, skipped) = parse_crontab("# comment\nMAILTO=\"\"\n\nPATH=/usr/bin\n");
+        assert!(entries.is_empty());
+        assert!(skipped.is_empty());
+    }
+}
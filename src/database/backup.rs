@@ -0,0 +1,291 @@
+use super::{establish_connection, SCHEMA_VERSION};
+use crate::config;
+use chrono::{Local, NaiveDate};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::{ConnectOptions, Connection, Row, SqliteConnection};
+use std::path::{Path, PathBuf};
+
+/// Rows pointing at something that no longer exists, as (description, query
+/// counting them)
+const ORPHAN_CHECKS: &[(&str, &str)] = &[
+    (
+        "history entries of removed jobs",
+        "SELECT COUNT(*) FROM poke_history WHERE poke_id NOT IN (SELECT id FROM poke)",
+    ),
+    (
+        "calendar assignments of removed jobs",
+        "SELECT COUNT(*) FROM poke_calendar WHERE poke_id NOT IN (SELECT id FROM poke)",
+    ),
+    (
+        "calendar assignments of removed calendars",
+        "SELECT COUNT(*) FROM poke_calendar WHERE calendar_id NOT IN (SELECT id FROM calendar)",
+    ),
+    (
+        "dates of removed calendars",
+        "SELECT COUNT(*) FROM calendar_entry WHERE calendar_id NOT IN (SELECT id FROM calendar)",
+    ),
+    (
+        "follow-ups of removed jobs",
+        "SELECT COUNT(*) FROM poke_followup WHERE parent_id NOT IN (SELECT id FROM poke) \
+         OR child_id NOT IN (SELECT id FROM poke)",
+    ),
+    (
+        "queued follow-ups of removed jobs",
+        "SELECT COUNT(*) FROM followup_queue WHERE poke_id NOT IN (SELECT id FROM poke)",
+    ),
+    (
+        "focus cycles of removed sessions",
+        "SELECT COUNT(*) FROM focus_cycle WHERE session_id NOT IN (SELECT id FROM focus_session)",
+    ),
+];
+
+/// What a backup file contains, checked before restoring it
+#[derive(Debug, Clone)]
+pub struct BackupInfo {
+    /// `PRAGMA user_version`, 0 for backups made before it was set
+    pub schema_version: i64,
+    pub jobs: i64,
+}
+
+/// Result of `db check`
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    pub schema_version: i64,
+    /// Problems reported by `PRAGMA integrity_check`, empty when it passed
+    pub integrity_errors: Vec<String>,
+    /// Number of orphaned rows for each kind found
+    pub orphans: Vec<(&'static str, i64)>,
+}
+
+fn sql_string(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', "''"))
+}
+
+/// Write a consistent copy of the database with `VACUUM INTO`, which works
+/// while the service is using it. This stands in for SQLite's online backup
+/// API, which sqlx does not expose: it copies one read transaction's snapshot
+/// of the WAL database as well, and leaves a compacted file
+pub async fn backup_database(
+    pool: &SqlitePool,
+    dest: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if dest.exists() {
+        return Err(format!("{} already exists", dest.display()).into());
+    }
+    if let Some(dir) = dest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    sqlx::query(&format!("VACUUM INTO {}", sql_string(dest)))
+        .execute(pool)
+        .await
+        .map_err(|err| format!("Failed to back up to {}: {}", dest.display(), err))?;
+    Ok(())
+}
+
+async fn integrity_errors(conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query("PRAGMA integrity_check")
+        .fetch_all(conn)
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<String, _>(0))
+        .filter(|message| message != "ok")
+        .collect())
+}
+
+/// Open a backup read-only and check that it is an intact database of a
+/// schema this version can use
+pub async fn inspect_backup(path: &Path) -> Result<BackupInfo, Box<dyn std::error::Error>> {
+    if !path.is_file() {
+        return Err(format!("No backup found at {}", path.display()).into());
+    }
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+
+    let has_poke_table: i64 =
+        sqlx::query("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'poke'")
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|err| format!("{} is not a SQLite database: {}", path.display(), err))?
+            .get(0);
+    if has_poke_table == 0 {
+        return Err(format!("{} is not a poke_me database", path.display()).into());
+    }
+
+    let schema_version: i64 = sqlx::query("PRAGMA user_version")
+        .fetch_one(&mut conn)
+        .await?
+        .get(0);
+    if schema_version > SCHEMA_VERSION {
+        return Err(format!(
+            "{} was made by a newer version of poke_me (schema {}, this version supports up to {})",
+            path.display(),
+            schema_version,
+            SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    let errors = integrity_errors(&mut conn).await?;
+    if !errors.is_empty() {
+        return Err(format!(
+            "{} failed the integrity check: {}",
+            path.display(),
+            errors.join("; ")
+        )
+        .into());
+    }
+
+    let jobs: i64 = sqlx::query("SELECT COUNT(*) FROM poke")
+        .fetch_one(&mut conn)
+        .await?
+        .get(0);
+    conn.close().await?;
+    Ok(BackupInfo {
+        schema_version,
+        jobs,
+    })
+}
+
+/// Name of the database file without its extension, e.g. `poke.work`
+fn db_stem() -> String {
+    config::get()
+        .db
+        .file_stem()
+        .map_or("poke".to_string(), |stem| {
+            stem.to_string_lossy().to_string()
+        })
+}
+
+/// Replace the database with a backup checked by `inspect_backup`. The
+/// current database is backed up first, its path is returned. Nothing may be
+/// using the database
+pub async fn restore_database(
+    source: &Path,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    inspect_backup(source).await?;
+    let db = config::get().db.clone();
+
+    let saved = if db.exists() {
+        let saved = config::get().backup_dir.join(format!(
+            "{}-before-restore-{}.db",
+            db_stem(),
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(&db))
+            .await
+            .map_err(|err| format!("Failed to open {}: {}", db.display(), err))?;
+        let result = backup_database(&pool, &saved).await;
+        pool.close().await;
+        result.map_err(|err| format!("Current database not replaced. {}", err))?;
+        Some(saved)
+    } else {
+        None
+    };
+
+    // Copy next to the database first so that the switch is a single rename
+    let mut staging = db.clone().into_os_string();
+    staging.push(".restoring");
+    let staging = PathBuf::from(staging);
+    std::fs::copy(source, &staging)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut file = db.clone().into_os_string();
+        file.push(suffix);
+        match std::fs::remove_file(&file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    std::fs::rename(&staging, &db)?;
+
+    // Bring a backup made by an older version up to date
+    establish_connection().await?.close().await;
+    Ok(saved)
+}
+
+/// Run the integrity check and look for orphaned rows
+pub async fn check_database(pool: &SqlitePool) -> Result<CheckReport, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    let mut report = CheckReport {
+        integrity_errors: integrity_errors(&mut conn).await?,
+        ..CheckReport::default()
+    };
+    report.schema_version = sqlx::query("PRAGMA user_version")
+        .fetch_one(&mut *conn)
+        .await?
+        .get(0);
+    for (description, query) in ORPHAN_CHECKS {
+        let count: i64 = sqlx::query(query).fetch_one(&mut *conn).await?.get(0);
+        if count > 0 {
+            report.orphans.push((description, count));
+        }
+    }
+    Ok(report)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// Rebuild the database file to reclaim free space, returning its size
+/// before and after
+pub async fn vacuum_database(pool: &SqlitePool) -> Result<(u64, u64), sqlx::Error> {
+    let db = config::get().db.clone();
+    let before = file_size(&db);
+    sqlx::query("VACUUM").execute(pool).await?;
+    // The rebuilt pages go through the write-ahead log first
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await?;
+    Ok((before, file_size(&db)))
+}
+
+/// Date of a daily backup from its file name, `<stem>-YYYY-MM-DD.db`
+fn backup_date(stem: &str, file_name: &str) -> Option<NaiveDate> {
+    let date = file_name
+        .strip_prefix(stem)?
+        .strip_prefix('-')?
+        .strip_suffix(".db")?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Make today's backup unless it exists, then delete the oldest daily
+/// backups beyond `backup_keep`. Returns the new backup, if any
+pub async fn daily_backup(
+    pool: &SqlitePool,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let config = config::get();
+    if config.backup_keep == 0 {
+        return Ok(None);
+    }
+    let stem = db_stem();
+    let today = Local::now().date_naive();
+    let path = config
+        .backup_dir
+        .join(format!("{}-{}.db", stem, today.format("%Y-%m-%d")));
+    let created = if path.exists() {
+        None
+    } else {
+        backup_database(pool, &path).await?;
+        Some(path)
+    };
+
+    let mut backups: Vec<(NaiveDate, PathBuf)> = std::fs::read_dir(&config.backup_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let date = backup_date(&stem, &entry.file_name().to_string_lossy())?;
+            Some((date, entry.path()))
+        })
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(config.backup_keep);
+    for (_, old) in backups.drain(..excess) {
+        std::fs::remove_file(&old)
+            .map_err(|err| format!("Failed to remove old backup {}: {}", old.display(), err))?;
+    }
+    Ok(created)
+}
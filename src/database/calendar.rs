@@ -0,0 +1,286 @@
+use super::journal;
+use super::models::{Calendar, CalendarEntry};
+use crate::schedule::Exclusions;
+use chrono::{NaiveDate, Utc};
+use sqlx::{sqlite::SqlitePool, Row};
+
+/// Create the exclusion calendar tables
+pub(super) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS calendar (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            created TIMESTAMP NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS calendar_entry (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            calendar_id INTEGER NOT NULL,
+            start_date DATE NOT NULL,
+            end_date DATE NOT NULL,
+            summary TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS poke_calendar (
+            poke_id INTEGER NOT NULL,
+            calendar_id INTEGER NOT NULL,
+            PRIMARY KEY (poke_id, calendar_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Create a new, empty calendar
+pub async fn create_calendar(
+    pool: &SqlitePool,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if find_calendar(pool, name).await?.is_some() {
+        return Err(format!("A calendar with name '{}' already exists", name).into());
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO calendar (name, created) VALUES (?, ?)")
+        .bind(name)
+        .bind(Utc::now().naive_utc())
+        .execute(&mut *tx)
+        .await?;
+    journal::record_irreversible(&mut tx, &format!("create calendar '{}'", name)).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Delete a calendar together with its entries and job assignments
+pub async fn delete_calendar(
+    pool: &SqlitePool,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let calendar = get_calendar_by_name(pool, name).await?;
+
+    let mut tx = pool.begin().await?;
+    for query in [
+        "DELETE FROM calendar_entry WHERE calendar_id = ?",
+        "DELETE FROM poke_calendar WHERE calendar_id = ?",
+        "DELETE FROM calendar WHERE id = ?",
+    ] {
+        sqlx::query(query)
+            .bind(calendar.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    journal::record_irreversible(&mut tx, &format!("delete calendar '{}'", name)).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn list_calendars(pool: &SqlitePool) -> Result<Vec<Calendar>, sqlx::Error> {
+    sqlx::query_as::<_, Calendar>("SELECT * FROM calendar ORDER BY name")
+        .fetch_all(pool)
+        .await
+}
+
+async fn find_calendar(pool: &SqlitePool, name: &str) -> Result<Option<Calendar>, sqlx::Error> {
+    sqlx::query_as::<_, Calendar>("SELECT * FROM calendar WHERE name = ?")
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_calendar_by_name(
+    pool: &SqlitePool,
+    name: &str,
+) -> Result<Calendar, Box<dyn std::error::Error>> {
+    find_calendar(pool, name)
+        .await?
+        .ok_or_else(|| format!("No calendar found with name: {}", name).into())
+}
+
+/// Add an inclusive date range to a calendar
+pub async fn add_calendar_entry(
+    pool: &SqlitePool,
+    calendar_name: &str,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    summary: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if end_date < start_date {
+        return Err(format!("End date {} is before start date {}", end_date, start_date).into());
+    }
+    let calendar = get_calendar_by_name(pool, calendar_name).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO calendar_entry (calendar_id, start_date, end_date, summary) VALUES (?, ?, ?, ?)",
+    )
+    .bind(calendar.id)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(summary)
+    .execute(&mut *tx)
+    .await?;
+    journal::record_irreversible(
+        &mut tx,
+        &format!("add dates to calendar '{}'", calendar_name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Remove the entries of a calendar that start on the given date, returning how many were removed
+pub async fn remove_calendar_entry(
+    pool: &SqlitePool,
+    calendar_name: &str,
+    start_date: NaiveDate,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let calendar = get_calendar_by_name(pool, calendar_name).await?;
+
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query("DELETE FROM calendar_entry WHERE calendar_id = ? AND start_date = ?")
+        .bind(calendar.id)
+        .bind(start_date)
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(format!(
+            "No entry starting on {} in calendar '{}'",
+            start_date, calendar_name
+        )
+        .into());
+    }
+    journal::record_irreversible(
+        &mut tx,
+        &format!("remove dates from calendar '{}'", calendar_name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn list_calendar_entries(
+    pool: &SqlitePool,
+    calendar_id: i64,
+) -> Result<Vec<CalendarEntry>, sqlx::Error> {
+    sqlx::query_as::<_, CalendarEntry>(
+        "SELECT * FROM calendar_entry WHERE calendar_id = ? ORDER BY start_date",
+    )
+    .bind(calendar_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Assign a calendar to a job so the job skips its dates
+pub async fn assign_calendar(
+    pool: &SqlitePool,
+    calendar_name: &str,
+    poke_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let calendar = get_calendar_by_name(pool, calendar_name).await?;
+    let poke = super::get_poke_by_name(pool, poke_name).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT OR IGNORE INTO poke_calendar (poke_id, calendar_id) VALUES (?, ?)")
+        .bind(poke.id)
+        .bind(calendar.id)
+        .execute(&mut *tx)
+        .await?;
+    journal::record_irreversible(
+        &mut tx,
+        &format!("assign calendar '{}' to '{}'", calendar_name, poke_name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Stop applying a calendar to a job
+pub async fn unassign_calendar(
+    pool: &SqlitePool,
+    calendar_name: &str,
+    poke_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let calendar = get_calendar_by_name(pool, calendar_name).await?;
+    let poke = super::get_poke_by_name(pool, poke_name).await?;
+
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query("DELETE FROM poke_calendar WHERE poke_id = ? AND calendar_id = ?")
+        .bind(poke.id)
+        .bind(calendar.id)
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(format!(
+            "Calendar '{}' is not assigned to job '{}'",
+            calendar_name, poke_name
+        )
+        .into());
+    }
+    journal::record_irreversible(
+        &mut tx,
+        &format!("unassign calendar '{}' from '{}'", calendar_name, poke_name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Names of the calendars assigned to a job
+pub async fn get_poke_calendars(
+    pool: &SqlitePool,
+    poke_id: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT calendar.name FROM calendar \
+         JOIN poke_calendar ON poke_calendar.calendar_id = calendar.id \
+         WHERE poke_calendar.poke_id = ? ORDER BY calendar.name",
+    )
+    .bind(poke_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// All dates excluded for a job through its assigned calendars
+pub async fn get_poke_exclusions(
+    pool: &SqlitePool,
+    poke_id: i64,
+) -> Result<Exclusions, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT calendar_entry.start_date, calendar_entry.end_date FROM calendar_entry \
+         JOIN poke_calendar ON poke_calendar.calendar_id = calendar_entry.calendar_id \
+         WHERE poke_calendar.poke_id = ?",
+    )
+    .bind(poke_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Exclusions::new(
+        rows.iter().map(|row| (row.get(0), row.get(1))).collect(),
+    ))
+}
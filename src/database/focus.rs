@@ -0,0 +1,164 @@
+use super::models::{FocusPhase, FocusSession, FocusState, FocusTotal};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use sqlx::{sqlite::SqlitePool, Row};
+
+/// Create the focus session tables
+pub(super) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS focus_session (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at TIMESTAMP NOT NULL,
+            work_secs INTEGER NOT NULL,
+            break_secs INTEGER NOT NULL,
+            long_break_secs INTEGER NOT NULL,
+            cycles INTEGER NOT NULL,
+            cycle INTEGER NOT NULL DEFAULT 1,
+            phase TEXT NOT NULL DEFAULT 'work',
+            phase_ends_at TIMESTAMP NOT NULL,
+            paused_at TIMESTAMP,
+            state TEXT NOT NULL DEFAULT 'running',
+            ended_at TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // One row per completed work phase, used for daily totals
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS focus_cycle (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id INTEGER NOT NULL,
+            completed_at TIMESTAMP NOT NULL,
+            work_secs INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Start a new focus session, unless one is already running or paused
+pub async fn start_focus_session(
+    pool: &SqlitePool,
+    work_secs: i64,
+    break_secs: i64,
+    long_break_secs: i64,
+    cycles: i64,
+) -> Result<FocusSession, Box<dyn std::error::Error>> {
+    if work_secs < 1 || break_secs < 0 || long_break_secs < 0 {
+        return Err("Work time must be positive and breaks cannot be negative".into());
+    }
+    if cycles < 1 {
+        return Err(format!("Cycles must be at least 1, got {}", cycles).into());
+    }
+    if get_active_focus_session(pool).await?.is_some() {
+        return Err("A focus session is already in progress, stop it first".into());
+    }
+
+    let now = Utc::now().naive_utc();
+    let id = sqlx::query(
+        "INSERT INTO focus_session (started_at, work_secs, break_secs, long_break_secs, cycles, \
+         cycle, phase, phase_ends_at, state) VALUES (?, ?, ?, ?, ?, 1, ?, ?, ?)",
+    )
+    .bind(now)
+    .bind(work_secs)
+    .bind(break_secs)
+    .bind(long_break_secs)
+    .bind(cycles)
+    .bind(FocusPhase::Work)
+    .bind(now + Duration::seconds(work_secs))
+    .bind(FocusState::Running)
+    .execute(pool)
+    .await?
+    .last_insert_rowid();
+
+    let session = sqlx::query_as::<_, FocusSession>("SELECT * FROM focus_session WHERE id = ?")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    Ok(session)
+}
+
+/// The running or paused focus session, if any
+pub async fn get_active_focus_session(
+    pool: &SqlitePool,
+) -> Result<Option<FocusSession>, sqlx::Error> {
+    sqlx::query_as::<_, FocusSession>(
+        "SELECT * FROM focus_session WHERE state IN (?, ?) ORDER BY id DESC LIMIT 1",
+    )
+    .bind(FocusState::Running)
+    .bind(FocusState::Paused)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The most recent focus session, whatever its state
+pub async fn get_last_focus_session(
+    pool: &SqlitePool,
+) -> Result<Option<FocusSession>, sqlx::Error> {
+    sqlx::query_as::<_, FocusSession>("SELECT * FROM focus_session ORDER BY id DESC LIMIT 1")
+        .fetch_optional(pool)
+        .await
+}
+
+/// Save a session's progress along with the work phases it completed
+pub async fn save_focus_progress(
+    pool: &SqlitePool,
+    session: &FocusSession,
+    completed_work: &[NaiveDateTime],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE focus_session SET cycle = ?, phase = ?, phase_ends_at = ?, paused_at = ?, \
+         state = ?, ended_at = ? WHERE id = ?",
+    )
+    .bind(session.cycle)
+    .bind(session.phase)
+    .bind(session.phase_ends_at)
+    .bind(session.paused_at)
+    .bind(session.state)
+    .bind(session.ended_at)
+    .bind(session.id)
+    .execute(&mut *tx)
+    .await?;
+
+    for completed_at in completed_work {
+        sqlx::query(
+            "INSERT INTO focus_cycle (session_id, completed_at, work_secs) VALUES (?, ?, ?)",
+        )
+        .bind(session.id)
+        .bind(completed_at)
+        .bind(session.work_secs)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Completed focus time per local day since `since`, most recent first
+pub async fn get_focus_totals(
+    pool: &SqlitePool,
+    since: NaiveDate,
+) -> Result<Vec<FocusTotal>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT date(completed_at, 'localtime') AS day, COUNT(*), SUM(work_secs) FROM focus_cycle \
+         WHERE date(completed_at, 'localtime') >= ? GROUP BY day ORDER BY day DESC",
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| FocusTotal {
+            date: row.get(0),
+            cycles: row.get(1),
+            work_secs: row.get(2),
+        })
+        .collect())
+}
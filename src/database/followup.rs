@@ -0,0 +1,206 @@
+use super::get_poke_by_name;
+use super::journal;
+use super::models::{FollowUp, FollowUpTrigger, Poke};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{sqlite::SqlitePool, Sqlite, Transaction};
+
+/// Create the follow-up link and queue tables
+pub(super) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS poke_followup (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER NOT NULL,
+            child_id INTEGER NOT NULL,
+            delay_secs INTEGER NOT NULL,
+            trigger TEXT NOT NULL DEFAULT 'fire',
+            UNIQUE (parent_id, child_id, trigger)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Follow-ups waiting for their delay, kept in the database so they survive
+    // service restarts and can be queued by the CLI
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS followup_queue (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            poke_id INTEGER NOT NULL,
+            due_at TIMESTAMP NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Make `child` fire `delay_secs` after `parent` fires or is acknowledged
+pub async fn add_followup(
+    pool: &SqlitePool,
+    parent: &str,
+    child: &str,
+    delay_secs: i64,
+    trigger: FollowUpTrigger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if delay_secs < 0 {
+        return Err("Follow-up delay cannot be negative".into());
+    }
+    let parent = get_poke_by_name(pool, parent).await?;
+    let child = get_poke_by_name(pool, child).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO poke_followup (parent_id, child_id, delay_secs, trigger) VALUES (?, ?, ?, ?) \
+         ON CONFLICT (parent_id, child_id, trigger) DO UPDATE SET delay_secs = excluded.delay_secs",
+    )
+    .bind(parent.id)
+    .bind(child.id)
+    .bind(delay_secs)
+    .bind(trigger)
+    .execute(&mut *tx)
+    .await?;
+    journal::record_irreversible(
+        &mut tx,
+        &format!("link follow-up '{}' to '{}'", child.name, parent.name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Remove the follow-up links from `parent` to `child`
+pub async fn remove_followup(
+    pool: &SqlitePool,
+    parent: &str,
+    child: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let parent = get_poke_by_name(pool, parent).await?;
+    let child = get_poke_by_name(pool, child).await?;
+
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query("DELETE FROM poke_followup WHERE parent_id = ? AND child_id = ?")
+        .bind(parent.id)
+        .bind(child.id)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(format!(
+            "Job '{}' is not a follow-up of '{}'",
+            child.name, parent.name
+        )
+        .into());
+    }
+    journal::record_irreversible(
+        &mut tx,
+        &format!("unlink follow-up '{}' from '{}'", child.name, parent.name),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Follow-ups started by a job, with the child's name
+pub async fn get_followups(pool: &SqlitePool, poke_id: i64) -> Result<Vec<FollowUp>, sqlx::Error> {
+    sqlx::query_as::<_, FollowUp>(
+        "SELECT f.id, f.parent_id, f.child_id, f.delay_secs, f.trigger, p.name AS other_name \
+         FROM poke_followup f JOIN poke p ON p.id = f.child_id \
+         WHERE f.parent_id = ? AND p.deleted_at IS NULL ORDER BY f.delay_secs, p.name",
+    )
+    .bind(poke_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Links to a job from the jobs it follows up, with the parent's name
+pub async fn get_parents(pool: &SqlitePool, poke_id: i64) -> Result<Vec<FollowUp>, sqlx::Error> {
+    sqlx::query_as::<_, FollowUp>(
+        "SELECT f.id, f.parent_id, f.child_id, f.delay_secs, f.trigger, p.name AS other_name \
+         FROM poke_followup f JOIN poke p ON p.id = f.parent_id \
+         WHERE f.child_id = ? AND p.deleted_at IS NULL ORDER BY p.name",
+    )
+    .bind(poke_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Whether any follow-up waits for the job to be acknowledged
+pub async fn has_ack_followups(pool: &SqlitePool, poke_id: i64) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM poke_followup WHERE parent_id = ? AND trigger = ?",
+    )
+    .bind(poke_id)
+    .bind(FollowUpTrigger::Ack)
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Queue the follow-ups of a job for the given event
+pub async fn queue_followups(
+    pool: &SqlitePool,
+    poke_id: i64,
+    trigger: FollowUpTrigger,
+    at: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    queue_followups_tx(&mut tx, poke_id, trigger, at).await?;
+    tx.commit().await
+}
+
+/// Queue the follow-ups of a job within an existing transaction
+pub(super) async fn queue_followups_tx(
+    tx: &mut Transaction<'_, Sqlite>,
+    poke_id: i64,
+    trigger: FollowUpTrigger,
+    at: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO followup_queue (poke_id, due_at) \
+         SELECT child_id, datetime(?, '+' || delay_secs || ' seconds') \
+         FROM poke_followup WHERE parent_id = ? AND trigger = ?",
+    )
+    .bind(at)
+    .bind(poke_id)
+    .bind(trigger)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Take the queued follow-ups that are due, removing them from the queue
+pub async fn take_due_followups(pool: &SqlitePool) -> Result<Vec<Poke>, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+    let pokes = sqlx::query_as::<_, Poke>(
+        "SELECT p.* FROM followup_queue q JOIN poke p ON p.id = q.poke_id \
+         WHERE q.due_at <= ? AND p.deleted_at IS NULL ORDER BY q.due_at",
+    )
+    .bind(now)
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM followup_queue WHERE due_at <= ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(pokes)
+}
+
+/// Queued follow-ups of a job that have not fired yet
+pub async fn get_pending_followups(
+    pool: &SqlitePool,
+    poke_id: i64,
+) -> Result<Vec<NaiveDateTime>, sqlx::Error> {
+    sqlx::query_scalar("SELECT due_at FROM followup_queue WHERE poke_id = ? ORDER BY due_at")
+        .bind(poke_id)
+        .fetch_all(pool)
+        .await
+}
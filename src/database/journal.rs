@@ -0,0 +1,211 @@
+use super::models::{Poke, PokeStatus};
+use super::{delete_poke, write_poke};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqlitePool, Row, SqliteConnection};
+
+/// Number of operations kept for `undo`
+const JOURNAL_LIMIT: i64 = 100;
+
+/// Create the operation journal table
+pub(super) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    // One row per command that changed jobs, with the jobs as they were before.
+    // Changes undo cannot revert have no snapshots ("null")
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS operation_journal (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            performed_at TIMESTAMP NOT NULL,
+            description TEXT NOT NULL,
+            snapshots TEXT NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// A job row as it was before an operation, `None` if the operation created it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Snapshot {
+    id: i64,
+    before: Option<SavedPoke>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedPoke {
+    poke: Poke,
+    deleted_at: Option<NaiveDateTime>,
+}
+
+impl Snapshot {
+    /// Snapshot of a job the operation is about to create
+    pub(super) fn created(id: i64) -> Self {
+        Snapshot { id, before: None }
+    }
+
+    /// Snapshot of an existing job, trashed or not, before it is changed
+    pub(super) async fn of(conn: &mut SqliteConnection, id: i64) -> Result<Self, sqlx::Error> {
+        let poke = sqlx::query_as::<_, Poke>("SELECT * FROM poke WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let deleted_at: Option<NaiveDateTime> =
+            sqlx::query_scalar("SELECT deleted_at FROM poke WHERE id = ?")
+                .bind(id)
+                .fetch_one(conn)
+                .await?;
+        Ok(Snapshot {
+            id,
+            before: Some(SavedPoke { poke, deleted_at }),
+        })
+    }
+}
+
+/// Add an operation to the journal
+pub(super) async fn record(
+    conn: &mut SqliteConnection,
+    description: &str,
+    snapshots: &[Snapshot],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert(conn, description, &serde_json::to_string(snapshots)?).await?;
+    Ok(())
+}
+
+/// Add a change `undo` cannot revert, such as a calendar or follow-up change.
+/// `undo` stops there, as reverting job changes made before it could leave
+/// the calendars, follow-ups or timers it touched inconsistent
+pub(super) async fn record_irreversible(
+    conn: &mut SqliteConnection,
+    description: &str,
+) -> Result<(), sqlx::Error> {
+    // A run of such changes takes a single row, so that e.g. importing many
+    // calendar dates does not push the job changes out of the journal
+    let merged = sqlx::query(
+        "UPDATE operation_journal SET performed_at = ?, description = ? \
+         WHERE id = (SELECT MAX(id) FROM operation_journal) AND snapshots = 'null'",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(description)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if merged == 0 {
+        insert(conn, description, "null").await?;
+    }
+
+    Ok(())
+}
+
+/// Insert a journal row, dropping the oldest beyond `JOURNAL_LIMIT`
+async fn insert(
+    conn: &mut SqliteConnection,
+    description: &str,
+    snapshots: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO operation_journal (performed_at, description, snapshots) VALUES (?, ?, ?)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(description)
+    .bind(snapshots)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        "DELETE FROM operation_journal WHERE id <= (SELECT MAX(id) FROM operation_journal) - ?",
+    )
+    .bind(JOURNAL_LIMIT)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Revert the latest operation not undone yet, returning its description
+pub async fn undo_last(pool: &SqlitePool) -> Result<String, Box<dyn std::error::Error>> {
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query(
+        "SELECT id, description, snapshots FROM operation_journal \
+         WHERE undone = 0 ORDER BY id DESC LIMIT 1",
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or("Nothing to undo")?;
+    let operation_id: i64 = row.get(0);
+    let description: String = row.get(1);
+    let snapshots: Vec<Snapshot> = serde_json::from_str::<Option<Vec<Snapshot>>>(row.get(2))?
+        .ok_or_else(|| {
+            format!(
+                "Cannot undo past {}: changes to calendars, follow-ups and timers \
+                 cannot be undone",
+                description
+            )
+        })?;
+
+    // A purged job cannot come back. The operation is dropped so that the
+    // ones before it can still be undone
+    for saved in snapshots
+        .iter()
+        .filter_map(|snapshot| snapshot.before.as_ref())
+    {
+        let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM poke WHERE id = ?")
+            .bind(saved.poke.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if exists == 0 {
+            mark_undone(&mut tx, operation_id).await?;
+            tx.commit().await?;
+            return Err(format!(
+                "Cannot undo {}: job '{}' was purged from the trash. Run undo again for the change before it",
+                description, saved.poke.name
+            )
+            .into());
+        }
+    }
+
+    // Reversed, so that a job touched twice ends up as it was first
+    for snapshot in snapshots.iter().rev() {
+        match &snapshot.before {
+            None => delete_poke(&mut tx, snapshot.id).await?,
+            Some(saved) => {
+                // The job may have fired since, so its completion is decided
+                // again against the current history, as when editing it
+                let mut poke = saved.poke.clone();
+                let run_count: i64 =
+                    sqlx::query_scalar("SELECT COUNT(*) FROM poke_history WHERE poke_id = ?")
+                        .bind(poke.id)
+                        .fetch_one(&mut *tx)
+                        .await?;
+                if poke.status != PokeStatus::Paused {
+                    poke.status = if poke.is_exhausted(run_count, Utc::now().naive_utc()) {
+                        PokeStatus::Completed
+                    } else {
+                        PokeStatus::Active
+                    };
+                }
+                write_poke(&mut tx, &poke).await?;
+                sqlx::query("UPDATE poke SET deleted_at = ? WHERE id = ?")
+                    .bind(saved.deleted_at)
+                    .bind(snapshot.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
+    mark_undone(&mut tx, operation_id).await?;
+    tx.commit().await?;
+
+    Ok(description)
+}
+
+async fn mark_undone(conn: &mut SqliteConnection, operation_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE operation_journal SET undone = 1 WHERE id = ?")
+        .bind(operation_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
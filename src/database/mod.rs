@@ -1,25 +1,70 @@
-pub use models::Poke;
+pub use models::{
+    FireRecord, FollowUpTrigger, IntervalAnchor, MissedPolicy, Poke, PokeStats, PokeStatus, Urgency,
+};
+pub mod backup;
+pub mod calendar;
+pub mod focus;
+pub mod followup;
+pub mod journal;
 pub mod models;
+pub mod timer;
+pub mod trash;
+use crate::config;
 use crate::notification::setup_notification;
-use sqlx::{sqlite::SqlitePool, Row};
-use std::path::Path;
+use crate::schedule::validate_jitter;
+use crate::template;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use journal::Snapshot;
+use sqlx::{sqlite::SqlitePool, Row, SqliteConnection};
+use std::path::{Path, PathBuf};
 use tokio_cron_scheduler::JobScheduler;
 
+/// Version of the schema created by `run_migrations`, stored in the database
+/// as `PRAGMA user_version`. Bump it whenever `run_migrations` changes the
+/// schema
+pub const SCHEMA_VERSION: i64 = 15;
+
 /// Check if we're running in development mode (cargo run) vs production mode (installed binary)
-fn is_development_mode() -> bool {
-    // Check if the executable path contains "target" (indicating cargo run)
-    if let Ok(exe_path) = std::env::current_exe() {
-        exe_path.to_string_lossy().contains("target")
-    } else {
-        false
+pub(crate) fn is_development_mode() -> bool {
+    // Only a binary in <project>/target/{debug,release} next to a Cargo.toml
+    // counts, not any path that happens to contain "target"
+    let Ok(exe_path) = std::env::current_exe() else {
+        return false;
+    };
+    let Some(profile_dir) = exe_path.parent() else {
+        return false;
+    };
+    let Some(target_dir) = profile_dir.parent() else {
+        return false;
+    };
+    matches!(
+        profile_dir.file_name().and_then(|name| name.to_str()),
+        Some("debug" | "release")
+    ) && target_dir.file_name().is_some_and(|name| name == "target")
+        && target_dir
+            .parent()
+            .is_some_and(|project| project.join("Cargo.toml").exists())
+}
+
+/// Directory holding user-edited configuration such as the job file
+pub fn config_dir() -> PathBuf {
+    if is_development_mode() {
+        return PathBuf::from(".");
+    }
+    match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => Path::new(&dir).join("poke_me"),
+        _ => {
+            let home_dir = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            Path::new(&home_dir).join(".config/poke_me")
+        }
     }
 }
 
-pub async fn establish_connection() -> Result<SqlitePool, sqlx::Error> {
-    // Determine database path based on how the binary is being run
-    let db_path = if is_development_mode() {
+/// Directory holding the database and other state files
+pub fn data_dir() -> PathBuf {
+    if is_development_mode() {
         // Development mode (cargo run): use current directory
-        "poke.db".to_string()
+        PathBuf::from(".")
     } else {
         // Production mode (installed binary): use systemd service location
         let home_dir = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
@@ -30,13 +75,25 @@ pub async fn establish_connection() -> Result<SqlitePool, sqlx::Error> {
             std::fs::create_dir_all(&db_dir).expect("Failed to create poke_me data directory");
         }
 
-        db_dir.join("poke.db").to_string_lossy().to_string()
-    };
+        db_dir
+    }
+}
+
+pub async fn establish_connection() -> Result<SqlitePool, sqlx::Error> {
+    // The default location depends on how the binary is being run
+    let db_path = config::get().db.to_string_lossy().to_string();
 
     // Create database file if it doesn't exist
     if !Path::new(&db_path).exists() {
+        // A path given with --db or POKE_ME_DB may point into a new directory
+        if let Some(dir) = Path::new(&db_path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(dir)?;
+        }
         // Create empty database file
-        std::fs::File::create(&db_path).expect("Failed to create database file");
+        std::fs::File::create(&db_path)?;
     }
 
     // Connect to the database
@@ -48,7 +105,7 @@ pub async fn establish_connection() -> Result<SqlitePool, sqlx::Error> {
     Ok(pool)
 }
 
-async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+pub(crate) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     // Create the poke table if it doesn't exist
     sqlx::query(
         r#"
@@ -65,61 +122,249 @@ async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Change counter bumped by triggers so the service can detect edits made by
+    // any process (CLI, sqlite3 shell, synced database files)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS poke_changes (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            version INTEGER NOT NULL DEFAULT 0
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("INSERT OR IGNORE INTO poke_changes (id, version) VALUES (1, 0)")
+        .execute(pool)
+        .await?;
+
+    for event in ["INSERT", "UPDATE", "DELETE"] {
+        create_change_trigger(pool, event, "poke").await?;
+    }
+
+    add_column_if_missing(
+        pool,
+        "poke",
+        "missed_policy",
+        "TEXT NOT NULL DEFAULT 'skip'",
+    )
+    .await?;
+
+    add_column_if_missing(pool, "poke", "starts_at", "TIMESTAMP").await?;
+    add_column_if_missing(pool, "poke", "ends_at", "TIMESTAMP").await?;
+    add_column_if_missing(pool, "poke", "max_runs", "INTEGER").await?;
+    add_column_if_missing(pool, "poke", "status", "TEXT NOT NULL DEFAULT 'active'").await?;
+    add_column_if_missing(pool, "poke", "rrule", "TEXT").await?;
+    add_column_if_missing(pool, "poke", "urgency", "TEXT NOT NULL DEFAULT 'normal'").await?;
+    add_column_if_missing(pool, "poke", "jitter_secs", "INTEGER").await?;
+    add_column_if_missing(pool, "poke", "jitter_seed", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "poke", "interval_secs", "INTEGER").await?;
+    add_column_if_missing(
+        pool,
+        "poke",
+        "interval_anchor",
+        "TEXT NOT NULL DEFAULT 'fixed'",
+    )
+    .await?;
+    add_column_if_missing(pool, "poke", "anchor_at", "TIMESTAMP").await?;
+    add_column_if_missing(pool, "poke", "icon", "TEXT").await?;
+    add_column_if_missing(pool, "poke", "timeout_secs", "INTEGER").await?;
+    add_column_if_missing(pool, "poke", "sound", "TEXT").await?;
+    add_column_if_missing(pool, "poke", "category", "TEXT").await?;
+    // Set when a job is moved to the trash, trashed jobs are left out everywhere else
+    add_column_if_missing(pool, "poke", "deleted_at", "TIMESTAMP").await?;
+
+    // Every firing of a job, used for catch-up after downtime and statistics
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS poke_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            poke_id INTEGER NOT NULL,
+            fired_at TIMESTAMP NOT NULL,
+            missed INTEGER NOT NULL DEFAULT 0
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    add_column_if_missing(pool, "poke_history", "acked_at", "TIMESTAMP").await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS poke_history_poke_id ON poke_history (poke_id, fired_at)",
+    )
+    .execute(pool)
+    .await?;
+
+    calendar::run_migrations(pool).await?;
+    followup::run_migrations(pool).await?;
+    focus::run_migrations(pool).await?;
+    timer::run_migrations(pool).await?;
+    journal::run_migrations(pool).await?;
+
+    // Lets `db restore` tell whether a backup is newer than this binary
+    sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+        .execute(pool)
+        .await?;
+
     Ok(())
 }
 
-pub async fn add_poke<T>(
+/// Add a column to an existing table unless it is already there
+async fn add_column_if_missing(
     pool: &SqlitePool,
-    name: T,
-    cron: T,
-    detail: Option<T>,
-    sound_enabled: bool,
-    sched: &JobScheduler,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    T: Into<String>,
-{
-    let poke = Poke::new(name, cron, detail, sound_enabled)?;
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), sqlx::Error> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(pool)
+        .await?;
 
-    // Start a transaction
-    let mut tx = pool.begin().await?;
+    let exists = columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == column);
+    if !exists {
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Create a trigger bumping the change counter whenever `table` is modified
+async fn create_change_trigger(
+    pool: &SqlitePool,
+    event: &str,
+    table: &str,
+) -> Result<(), sqlx::Error> {
+    let trigger = format!(
+        "CREATE TRIGGER IF NOT EXISTS {}_{}_changes AFTER {} ON {} \
+         BEGIN UPDATE poke_changes SET version = version + 1 WHERE id = 1; END",
+        table,
+        event.to_lowercase(),
+        event,
+        table
+    );
+    sqlx::query(&trigger).execute(pool).await?;
+    Ok(())
+}
 
-    // Check if name already exists
-    let existing = sqlx::query("SELECT COUNT(*) FROM poke WHERE name = ?")
-        .bind(&poke.name)
-        .fetch_one(&mut *tx)
+/// Get the current value of the change counter
+pub async fn get_change_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("SELECT version FROM poke_changes WHERE id = 1")
+        .fetch_one(pool)
         .await?;
+    Ok(row.get(0))
+}
 
-    let count: i64 = existing.get(0);
-    if count > 0 {
-        return Err(format!("A job with name '{}' already exists", poke.name).into());
+/// Check the settings of a job that the schedule parser does not cover
+fn validate_poke(poke: &Poke) -> Result<(), Box<dyn std::error::Error>> {
+    poke.validate_limits()?;
+    validate_jitter(poke)?;
+    if let Some(detail) = &poke.detail {
+        template::validate(detail)?;
     }
+    Ok(())
+}
+
+/// Insert a job built with `Poke::new`, making sure it can be scheduled
+pub async fn add_poke(
+    pool: &SqlitePool,
+    poke: Poke,
+    sched: &JobScheduler,
+) -> Result<(), Box<dyn std::error::Error>> {
+    validate_poke(&poke)?;
+
+    // Start a transaction
+    let mut tx = pool.begin().await?;
 
-    // Insert the job
-    let _result = sqlx::query(
-        "INSERT INTO poke (name, cron, detail, sound_enabled, created) VALUES (?, ?, ?, ?, ?)",
+    check_name_free(&mut tx, &poke.name).await?;
+    let id = insert_poke(&mut tx, &poke).await?;
+    journal::record(
+        &mut tx,
+        &format!("add '{}'", poke.name),
+        &[Snapshot::created(id)],
     )
-    .bind(&poke.name)
-    .bind(&poke.cron)
-    .bind(&poke.detail)
-    .bind(&poke.sound_enabled)
-    .bind(&poke.created)
-    .execute(&mut *tx)
     .await?;
 
-    // Set up notification
-    match setup_notification(&poke, sched).await {
-        Ok(_) => {
+    // Make sure the job can be scheduled before committing. Only the message of
+    // a failure is kept so the future stays `Send` for the API
+    match setup_notification(pool, &poke, sched)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(handle) => {
+            handle.remove(sched).await?;
             tx.commit().await?;
             Ok(())
         }
-        Err(err) => {
+        Err(message) => {
             tx.rollback().await?;
-            Err(err)
+            Err(message.into())
         }
     }
 }
 
+/// Fail if a job uses the name, including a job in the trash
+async fn check_name_free(
+    conn: &mut SqliteConnection,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let existing: Option<Option<NaiveDateTime>> =
+        sqlx::query_scalar("SELECT deleted_at FROM poke WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&mut *conn)
+            .await?;
+    match existing {
+        Some(None) => Err(format!("A job with name '{}' already exists", name).into()),
+        Some(Some(_)) => Err(format!(
+            "A job with name '{}' is in the trash. Bring it back with 'poke_me restore {}' \
+             or delete it for good with 'poke_me trash purge {}'",
+            name, name, name
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Insert a new row for a job, ignoring its id. Returns the id of the new row
+async fn insert_poke(conn: &mut SqliteConnection, poke: &Poke) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO poke (name, cron, detail, sound_enabled, created, missed_policy, starts_at, ends_at, max_runs, status, rrule, urgency, jitter_secs, jitter_seed, interval_secs, interval_anchor, anchor_at, icon, timeout_secs, sound, category) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&poke.name)
+    .bind(&poke.cron)
+    .bind(&poke.detail)
+    .bind(poke.sound_enabled)
+    .bind(poke.created)
+    .bind(poke.missed_policy)
+    .bind(poke.starts_at)
+    .bind(poke.ends_at)
+    .bind(poke.max_runs)
+    .bind(poke.status)
+    .bind(&poke.rrule)
+    .bind(poke.urgency)
+    .bind(poke.jitter_secs)
+    .bind(poke.jitter_seed)
+    .bind(poke.interval_secs)
+    .bind(poke.interval_anchor)
+    .bind(poke.anchor_at)
+    .bind(&poke.icon)
+    .bind(poke.timeout_secs)
+    .bind(&poke.sound)
+    .bind(&poke.category)
+    .execute(conn)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
 pub async fn list_pokes(pool: &SqlitePool, head: Option<i32>) -> Result<Vec<Poke>, sqlx::Error> {
     let limit_clause = if let Some(limit) = head {
         format!(" LIMIT {}", limit)
@@ -127,7 +372,10 @@ pub async fn list_pokes(pool: &SqlitePool, head: Option<i32>) -> Result<Vec<Poke
         String::new()
     };
 
-    let query = format!("SELECT * FROM poke ORDER BY created DESC{}", limit_clause);
+    let query = format!(
+        "SELECT * FROM poke WHERE deleted_at IS NULL ORDER BY created DESC{}",
+        limit_clause
+    );
     sqlx::query_as::<_, Poke>(&query).fetch_all(pool).await
 }
 
@@ -135,10 +383,11 @@ pub async fn get_poke_by_name(
     pool: &SqlitePool,
     name: &str,
 ) -> Result<Poke, Box<dyn std::error::Error>> {
-    let poke = sqlx::query_as::<_, Poke>("SELECT * FROM poke WHERE name = ?")
-        .bind(name)
-        .fetch_optional(pool)
-        .await?;
+    let poke =
+        sqlx::query_as::<_, Poke>("SELECT * FROM poke WHERE name = ? AND deleted_at IS NULL")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
 
     poke.ok_or_else(|| format!("No job found with name: {}", name).into())
 }
@@ -148,29 +397,61 @@ pub async fn search_pokes_by_name(
     search_term: &str,
 ) -> Result<Vec<Poke>, sqlx::Error> {
     let search_pattern = format!("%{}%", search_term);
-    sqlx::query_as::<_, Poke>("SELECT * FROM poke WHERE name LIKE ? ORDER BY created DESC")
-        .bind(search_pattern)
-        .fetch_all(pool)
-        .await
+    sqlx::query_as::<_, Poke>(
+        "SELECT * FROM poke WHERE name LIKE ? AND deleted_at IS NULL ORDER BY created DESC",
+    )
+    .bind(search_pattern)
+    .fetch_all(pool)
+    .await
 }
 
-/// Remove a job by name
+/// Move a job to the trash by name
 pub async fn remove_poke(pool: &SqlitePool, name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Check if the job exists first
-    let existing = sqlx::query("SELECT COUNT(*) FROM poke WHERE name = ?")
-        .bind(name)
-        .fetch_one(pool)
+    let poke = get_poke_by_name(pool, name).await?;
+
+    let mut tx = pool.begin().await?;
+    let snapshot = Snapshot::of(&mut tx, poke.id).await?;
+    trash_poke(&mut tx, poke.id).await?;
+    journal::record(&mut tx, &format!("remove '{}'", name), &[snapshot]).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Mark a job as trashed, keeping its calendars, follow-ups and history for a
+/// restore
+async fn trash_poke(conn: &mut SqliteConnection, poke_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE poke SET deleted_at = ? WHERE id = ?")
+        .bind(Utc::now().naive_utc())
+        .bind(poke_id)
+        .execute(conn)
         .await?;
 
-    let count: i64 = existing.get(0);
-    if count == 0 {
-        return Err(format!("No job found with name '{}'", name).into());
-    }
+    Ok(())
+}
 
-    // Delete the job
-    let _result = sqlx::query("DELETE FROM poke WHERE name = ?")
-        .bind(name)
-        .execute(pool)
+/// Permanently delete a job along with its calendar assignments, follow-ups
+/// and history
+async fn delete_poke(conn: &mut SqliteConnection, poke_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM poke_calendar WHERE poke_id = ?")
+        .bind(poke_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM poke_followup WHERE parent_id = ?1 OR child_id = ?1")
+        .bind(poke_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM followup_queue WHERE poke_id = ?")
+        .bind(poke_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM poke_history WHERE poke_id = ?")
+        .bind(poke_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM poke WHERE id = ?")
+        .bind(poke_id)
+        .execute(conn)
         .await?;
 
     Ok(())
@@ -181,24 +462,312 @@ pub async fn toggle_poke_sound(
     pool: &SqlitePool,
     name: &str,
 ) -> Result<bool, Box<dyn std::error::Error>> {
-    // Check if the job exists first
-    let existing = sqlx::query("SELECT sound_enabled FROM poke WHERE name = ?")
-        .bind(name)
-        .fetch_optional(pool)
+    let mut poke = get_poke_by_name(pool, name).await?;
+
+    // Toggle the sound setting
+    poke.sound_enabled = !poke.sound_enabled;
+
+    let mut tx = pool.begin().await?;
+    let snapshot = Snapshot::of(&mut tx, poke.id).await?;
+    sqlx::query("UPDATE poke SET sound_enabled = ? WHERE id = ?")
+        .bind(poke.sound_enabled)
+        .bind(poke.id)
+        .execute(&mut *tx)
         .await?;
+    journal::record(&mut tx, &format!("toggle sound of '{}'", name), &[snapshot]).await?;
+    tx.commit().await?;
 
-    let current_sound = existing.ok_or_else(|| format!("No job found with name '{}'", name))?;
-    let current_sound_enabled: bool = current_sound.get(0);
+    Ok(poke.sound_enabled)
+}
 
-    // Toggle the sound setting
-    let new_sound_enabled = !current_sound_enabled;
+/// Write back the editable settings of an existing job, matched by id
+pub async fn update_poke(pool: &SqlitePool, poke: &Poke) -> Result<(), Box<dyn std::error::Error>> {
+    validate_poke(poke)?;
+
+    let mut tx = pool.begin().await?;
+    let snapshot = live_snapshot(&mut tx, poke).await?;
+    write_poke(&mut tx, poke).await?;
+    journal::record(&mut tx, &format!("update '{}'", poke.name), &[snapshot]).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Snapshot of a job that must not be in the trash
+async fn live_snapshot(
+    conn: &mut SqliteConnection,
+    poke: &Poke,
+) -> Result<Snapshot, Box<dyn std::error::Error>> {
+    let live: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM poke WHERE id = ? AND deleted_at IS NULL")
+            .bind(poke.id)
+            .fetch_one(&mut *conn)
+            .await?;
+    if live == 0 {
+        return Err(format!("No job found with name '{}'", poke.name).into());
+    }
+    Ok(Snapshot::of(conn, poke.id).await?)
+}
 
-    // Update the job
-    let _result = sqlx::query("UPDATE poke SET sound_enabled = ? WHERE name = ?")
-        .bind(new_sound_enabled)
-        .bind(name)
+/// Update a job's row, returning the number of rows changed
+async fn write_poke(conn: &mut SqliteConnection, poke: &Poke) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE poke SET cron = ?, rrule = ?, detail = ?, sound_enabled = ?, missed_policy = ?, \
+         starts_at = ?, ends_at = ?, max_runs = ?, status = ?, urgency = ?, \
+         jitter_secs = ?, jitter_seed = ?, interval_secs = ?, interval_anchor = ?, anchor_at = ?, \
+         icon = ?, timeout_secs = ?, sound = ?, category = ? WHERE id = ?",
+    )
+    .bind(&poke.cron)
+    .bind(&poke.rrule)
+    .bind(&poke.detail)
+    .bind(poke.sound_enabled)
+    .bind(poke.missed_policy)
+    .bind(poke.starts_at)
+    .bind(poke.ends_at)
+    .bind(poke.max_runs)
+    .bind(poke.status)
+    .bind(poke.urgency)
+    .bind(poke.jitter_secs)
+    .bind(poke.jitter_seed)
+    .bind(poke.interval_secs)
+    .bind(poke.interval_anchor)
+    .bind(poke.anchor_at)
+    .bind(&poke.icon)
+    .bind(poke.timeout_secs)
+    .bind(&poke.sound)
+    .bind(&poke.category)
+    .bind(poke.id)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Create, update and delete jobs in a single transaction, so that either all
+/// changes are applied or none. Every created or changed job is checked the
+/// same way `add_poke` and `update_poke` do before anything is written
+pub async fn apply_poke_changes(
+    pool: &SqlitePool,
+    create: &[Poke],
+    update: &[Poke],
+    delete: &[String],
+    sched: &JobScheduler,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for poke in create.iter().chain(update) {
+        validate_poke(poke).map_err(|err| format!("Job '{}': {}", poke.name, err))?;
+        let handle = setup_notification(pool, poke, sched)
+            .await
+            .map_err(|err| format!("Job '{}': {}", poke.name, err))?;
+        handle.remove(sched).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut snapshots = Vec::new();
+    for name in delete {
+        let id: i64 =
+            sqlx::query_scalar("SELECT id FROM poke WHERE name = ? AND deleted_at IS NULL")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| format!("No job found with name '{}'", name))?;
+        snapshots.push(Snapshot::of(&mut tx, id).await?);
+        trash_poke(&mut tx, id).await?;
+    }
+    for poke in update {
+        snapshots.push(live_snapshot(&mut tx, poke).await?);
+        write_poke(&mut tx, poke).await?;
+    }
+    for poke in create {
+        check_name_free(&mut tx, &poke.name).await?;
+        let id = insert_poke(&mut tx, poke).await?;
+        snapshots.push(Snapshot::created(id));
+    }
+    let description = format!(
+        "apply ({} added, {} changed, {} removed)",
+        create.len(),
+        update.len(),
+        delete.len()
+    );
+    journal::record(&mut tx, &description, &snapshots).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Change the lifecycle state of a job
+pub async fn set_poke_status(
+    pool: &SqlitePool,
+    poke_id: i64,
+    status: PokeStatus,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE poke SET status = ? WHERE id = ?")
+        .bind(status)
+        .bind(poke_id)
         .execute(pool)
         .await?;
 
-    Ok(new_sound_enabled)
+    Ok(())
+}
+
+/// Pause an active job until it is resumed
+pub async fn pause_poke(pool: &SqlitePool, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let poke = get_poke_by_name(pool, name).await?;
+    match poke.status {
+        PokeStatus::Active => {
+            change_status(
+                pool,
+                &poke,
+                PokeStatus::Paused,
+                &format!("pause '{}'", name),
+            )
+            .await
+        }
+        PokeStatus::Paused => Err(format!("Job '{}' is already paused", name).into()),
+        PokeStatus::Completed => Err(format!("Job '{}' is completed", name).into()),
+    }
+}
+
+/// Resume a paused job, completing it instead if its limits ran out meanwhile.
+/// Returns the new status
+pub async fn resume_poke(
+    pool: &SqlitePool,
+    name: &str,
+) -> Result<PokeStatus, Box<dyn std::error::Error>> {
+    let poke = get_poke_by_name(pool, name).await?;
+    if poke.status != PokeStatus::Paused {
+        return Err(format!("Job '{}' is not paused", name).into());
+    }
+    let stats = get_poke_stats(pool, poke.id).await?;
+    let status = if poke.is_exhausted(stats.run_count, chrono::Utc::now().naive_utc()) {
+        PokeStatus::Completed
+    } else {
+        PokeStatus::Active
+    };
+    change_status(pool, &poke, status, &format!("resume '{}'", name)).await?;
+    Ok(status)
+}
+
+/// Change the state of a job on request, recording it for `undo`
+async fn change_status(
+    pool: &SqlitePool,
+    poke: &Poke,
+    status: PokeStatus,
+    description: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tx = pool.begin().await?;
+    let snapshot = Snapshot::of(&mut tx, poke.id).await?;
+    sqlx::query("UPDATE poke SET status = ? WHERE id = ?")
+        .bind(status)
+        .bind(poke.id)
+        .execute(&mut *tx)
+        .await?;
+    journal::record(&mut tx, description, &[snapshot]).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Record that a job fired, `missed` being the number of occurrences it stands in for
+pub async fn record_fire(
+    pool: &SqlitePool,
+    poke_id: i64,
+    fired_at: NaiveDateTime,
+    missed: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO poke_history (poke_id, fired_at, missed) VALUES (?, ?, ?)")
+        .bind(poke_id)
+        .bind(fired_at)
+        .bind(missed)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// The latest firings of a job, most recent first
+pub async fn get_poke_history(
+    pool: &SqlitePool,
+    poke_id: i64,
+    limit: i64,
+) -> Result<Vec<FireRecord>, sqlx::Error> {
+    sqlx::query_as::<_, FireRecord>(
+        "SELECT fired_at, missed, acked_at FROM poke_history WHERE poke_id = ? \
+         ORDER BY fired_at DESC, id DESC LIMIT ?",
+    )
+    .bind(poke_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Local days on which a job fired, most recent first
+pub async fn get_fire_days(pool: &SqlitePool, poke_id: i64) -> Result<Vec<NaiveDate>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT DISTINCT date(fired_at, 'localtime') AS day FROM poke_history \
+         WHERE poke_id = ? ORDER BY day DESC",
+    )
+    .bind(poke_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Get firing statistics for a job
+pub async fn get_poke_stats(pool: &SqlitePool, poke_id: i64) -> Result<PokeStats, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT COUNT(*), MAX(fired_at), MAX(acked_at) FROM poke_history WHERE poke_id = ?",
+    )
+    .bind(poke_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(PokeStats {
+        run_count: row.get(0),
+        last_fired: row.get(1),
+        last_acked: row.get(2),
+    })
+}
+
+/// Record that the latest notification of a job was acknowledged. Intervals
+/// anchored on acknowledgement restart from this moment and follow-ups waiting
+/// for the acknowledgement are queued
+pub async fn acknowledge_poke(
+    pool: &SqlitePool,
+    poke: &Poke,
+    acked_at: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE poke_history SET acked_at = ? \
+         WHERE id = (SELECT MAX(id) FROM poke_history WHERE poke_id = ?)",
+    )
+    .bind(acked_at)
+    .bind(poke.id)
+    .execute(&mut *tx)
+    .await?;
+
+    if poke.interval_secs.is_some() && poke.interval_anchor == IntervalAnchor::Ack {
+        sqlx::query("UPDATE poke SET anchor_at = ? WHERE id = ?")
+            .bind(acked_at)
+            .bind(poke.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    followup::queue_followups_tx(&mut tx, poke.id, FollowUpTrigger::Ack, acked_at).await?;
+    tx.commit().await
+}
+
+/// Restart the intervals anchored on service start, called when the service starts
+pub async fn anchor_start_intervals(
+    pool: &SqlitePool,
+    started_at: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE poke SET anchor_at = ? WHERE interval_secs IS NOT NULL AND interval_anchor = ?",
+    )
+    .bind(started_at)
+    .bind(IntervalAnchor::Start)
+    .execute(pool)
+    .await?;
+
+    Ok(())
 }
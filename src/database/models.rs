@@ -1,15 +1,374 @@
-use chrono::{NaiveDateTime, Utc};
+use crate::cron_expr::{normalize_cron, scheduler_cron};
+use crate::rrule::{is_rrule, RRule};
+use crate::schedule::{format_duration, parse_duration, Jitter};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+/// What to do with occurrences missed while the service was stopped or the machine suspended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MissedPolicy {
+    /// Drop missed occurrences silently
+    #[default]
+    Skip,
+    /// Fire a single notification summarising how many were missed
+    Once,
+    /// Fire one notification per missed occurrence
+    Each,
+}
+
+impl fmt::Display for MissedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            MissedPolicy::Skip => "skip",
+            MissedPolicy::Once => "once",
+            MissedPolicy::Each => "each",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for MissedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(MissedPolicy::Skip),
+            "once" => Ok(MissedPolicy::Once),
+            "each" => Ok(MissedPolicy::Each),
+            _ => Err(format!(
+                "Invalid missed policy: {}. Expected one of: skip, once, each",
+                s
+            )),
+        }
+    }
+}
+
+/// Lifecycle state of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PokeStatus {
+    /// The job is scheduled and fires normally
+    #[default]
+    Active,
+    /// The job reached its end date or maximum number of runs
+    Completed,
+    /// The job was paused and does not fire until resumed
+    Paused,
+}
+
+impl fmt::Display for PokeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            PokeStatus::Active => "active",
+            PokeStatus::Completed => "completed",
+            PokeStatus::Paused => "paused",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// Schedule keyword for jobs that only fire as follow-ups of other jobs
+pub const MANUAL_SCHEDULE: &str = "manual";
+
+/// How urgently the desktop should present a job's notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl fmt::Display for Urgency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for Urgency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" | "0" => Ok(Urgency::Low),
+            "normal" | "1" => Ok(Urgency::Normal),
+            "critical" | "2" => Ok(Urgency::Critical),
+            _ => Err(format!(
+                "Invalid urgency: {}. Expected one of: low, normal, critical",
+                s
+            )),
+        }
+    }
+}
+
+/// What an interval schedule counts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum IntervalAnchor {
+    /// A fixed cadence from when the job was created
+    #[default]
+    Fixed,
+    /// Restart the interval whenever a notification is acknowledged
+    Ack,
+    /// Restart the interval whenever the service starts
+    Start,
+}
+
+impl fmt::Display for IntervalAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            IntervalAnchor::Fixed => "fixed",
+            IntervalAnchor::Ack => "ack",
+            IntervalAnchor::Start => "start",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for IntervalAnchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fixed" => Ok(IntervalAnchor::Fixed),
+            "ack" => Ok(IntervalAnchor::Ack),
+            "start" => Ok(IntervalAnchor::Start),
+            _ => Err(format!(
+                "Invalid interval anchor: {}. Expected one of: fixed, ack, start",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
 pub struct Poke {
     pub id: i64,
     pub name: String,
+    /// Cron expression, empty when the job is scheduled by `rrule` or `interval_secs`
     pub cron: String,
     pub detail: Option<String>,
     pub sound_enabled: bool,
     pub created: NaiveDateTime,
+    pub missed_policy: MissedPolicy,
+    /// Optional UTC time before which the job does not fire
+    pub starts_at: Option<NaiveDateTime>,
+    /// Optional UTC time after which the job is completed
+    pub ends_at: Option<NaiveDateTime>,
+    /// Optional number of runs after which the job is completed
+    pub max_runs: Option<i64>,
+    pub status: PokeStatus,
+    /// RFC 5545 recurrence rule used instead of `cron`
+    pub rrule: Option<String>,
+    pub urgency: Urgency,
+    /// Optional window in seconds within which each occurrence is randomly delayed
+    pub jitter_secs: Option<i64>,
+    /// Seed making the random delays reproducible
+    pub jitter_seed: i64,
+    /// Fire every this many seconds instead of following `cron`
+    pub interval_secs: Option<i64>,
+    pub interval_anchor: IntervalAnchor,
+    /// UTC time the interval counts from, defaulting to `created`
+    pub anchor_at: Option<NaiveDateTime>,
+    /// Icon name from the icon theme or path to an image, defaulting to the
+    /// configured `default_icon`
+    pub icon: Option<String>,
+    /// Seconds before the notification expires, 0 for never, `None` for the server default
+    pub timeout_secs: Option<i64>,
+    /// Sound name from the sound theme or path to a sound file
+    pub sound: Option<String>,
+    /// Notification category hint such as "reminder" or "email.arrived"
+    pub category: Option<String>,
+}
+
+/// Event of a parent job that starts a follow-up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FollowUpTrigger {
+    /// The parent's notification was shown
+    #[default]
+    Fire,
+    /// The parent's notification was acknowledged
+    Ack,
+}
+
+impl fmt::Display for FollowUpTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            FollowUpTrigger::Fire => "fire",
+            FollowUpTrigger::Ack => "ack",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for FollowUpTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fire" => Ok(FollowUpTrigger::Fire),
+            "ack" => Ok(FollowUpTrigger::Ack),
+            _ => Err(format!(
+                "Invalid follow-up trigger: {}. Expected one of: fire, ack",
+                s
+            )),
+        }
+    }
+}
+
+/// Phase of a focus session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FocusPhase {
+    #[default]
+    Work,
+    Break,
+    LongBreak,
+}
+
+impl fmt::Display for FocusPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            FocusPhase::Work => "work",
+            FocusPhase::Break => "break",
+            FocusPhase::LongBreak => "long break",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// Lifecycle state of a focus session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FocusState {
+    #[default]
+    Running,
+    Paused,
+    /// Ended early with `focus stop`
+    Stopped,
+    /// All cycles done
+    Completed,
+}
+
+impl fmt::Display for FocusState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            FocusState::Running => "running",
+            FocusState::Paused => "paused",
+            FocusState::Stopped => "stopped",
+            FocusState::Completed => "completed",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// A pomodoro-style session alternating work and break phases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FocusSession {
+    pub id: i64,
+    pub started_at: NaiveDateTime,
+    pub work_secs: i64,
+    pub break_secs: i64,
+    pub long_break_secs: i64,
+    pub cycles: i64,
+    /// Current cycle, starting at 1
+    pub cycle: i64,
+    pub phase: FocusPhase,
+    pub phase_ends_at: NaiveDateTime,
+    /// When the session was paused, while it is paused
+    pub paused_at: Option<NaiveDateTime>,
+    pub state: FocusState,
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+/// An ephemeral countdown timer, removed once it fires or is cancelled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Timer {
+    pub id: i64,
+    pub message: String,
+    pub created: NaiveDateTime,
+    pub due_at: NaiveDateTime,
+}
+
+/// Focus time completed on one local day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusTotal {
+    pub date: NaiveDate,
+    pub cycles: i64,
+    pub work_secs: i64,
+}
+
+/// One firing of a job from its history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FireRecord {
+    pub fired_at: NaiveDateTime,
+    /// Number of occurrences the firing stood in for after downtime
+    pub missed: i64,
+    pub acked_at: Option<NaiveDateTime>,
+}
+
+/// Firing statistics for a job, derived from its history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PokeStats {
+    pub run_count: i64,
+    pub last_fired: Option<NaiveDateTime>,
+    pub last_acked: Option<NaiveDateTime>,
+}
+
+/// A job in the trash, with when it was removed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct TrashedPoke {
+    #[sqlx(flatten)]
+    pub poke: Poke,
+    pub deleted_at: NaiveDateTime,
+}
+
+/// A named set of dates on which assigned jobs do not fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Calendar {
+    pub id: i64,
+    pub name: String,
+    pub created: NaiveDateTime,
+}
+
+/// A job that fires a delay after another job fires or is acknowledged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FollowUp {
+    pub id: i64,
+    pub parent_id: i64,
+    pub child_id: i64,
+    pub delay_secs: i64,
+    pub trigger: FollowUpTrigger,
+    /// Name of the job on the other end of the link, filled in by queries
+    pub other_name: String,
+}
+
+/// An inclusive range of local dates excluded by a calendar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct CalendarEntry {
+    pub id: i64,
+    pub calendar_id: i64,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub summary: Option<String>,
 }
 
 impl Poke {
@@ -21,11 +380,29 @@ impl Poke {
     ) -> Result<Self, String> {
         let cron_str = cron.into();
         let name_str = name.into();
+        let created = Utc::now().naive_local();
 
-        // Validate cron expression using tokio-cron-scheduler's format (6 fields: second, minute, hour, day, month, weekday)
-        if !is_valid_cron(&cron_str) {
-            return Err(format!("Invalid cron expression: {}. Expected format: 'second minute hour day month weekday'", cron_str));
-        }
+        // RRULEs are stored normalised with an explicit DTSTART, defaulting to the start of today
+        let mut interval_secs = None;
+        let (cron_str, rrule) = if is_rrule(&cron_str) {
+            let today = Local::now().date_naive().and_time(NaiveTime::MIN);
+            let rule = RRule::parse(&cron_str, today)?;
+            (String::new(), Some(rule.to_string()))
+        } else if let Some(interval) = parse_interval(&cron_str) {
+            interval_secs = Some(interval?);
+            (String::new(), None)
+        } else if cron_str.trim().eq_ignore_ascii_case(MANUAL_SCHEDULE) {
+            // Only fired as a follow-up of another job
+            (String::new(), None)
+        } else {
+            // 5-field crontab lines and @macros are stored in the scheduler's 6-field form,
+            // with an optional trailing year
+            let cron_str = normalize_cron(&cron_str)?;
+            if !is_valid_cron(&scheduler_cron(&cron_str)) {
+                return Err(format!("Invalid cron expression: {}. Expected format: 'second minute hour day month weekday'", cron_str));
+            }
+            (cron_str, None)
+        };
 
         Ok(Poke {
             id: 0, // Will be set by database
@@ -33,9 +410,140 @@ impl Poke {
             cron: cron_str,
             detail: detail.map(|d| d.into()),
             sound_enabled,
-            created: Utc::now().naive_local(),
+            created,
+            missed_policy: MissedPolicy::default(),
+            starts_at: None,
+            ends_at: None,
+            max_runs: None,
+            status: PokeStatus::default(),
+            rrule,
+            urgency: Urgency::default(),
+            jitter_secs: None,
+            jitter_seed: uuid::Uuid::new_v4().as_u64_pair().0 as i64,
+            interval_secs,
+            interval_anchor: IntervalAnchor::default(),
+            anchor_at: None,
+            icon: None,
+            timeout_secs: None,
+            sound: None,
+            category: None,
         })
     }
+
+    /// Replace the schedule with a cron expression, RRULE, interval or "manual",
+    /// parsed the same way as when the job is added
+    pub fn set_schedule(&mut self, schedule: &str) -> Result<(), String> {
+        let parsed = Poke::new(self.name.as_str(), schedule, None, false)?;
+        self.cron = parsed.cron;
+        self.rrule = parsed.rrule;
+        self.interval_secs = parsed.interval_secs;
+        Ok(())
+    }
+
+    /// The schedule expression as shown to users
+    pub fn schedule_expr(&self) -> String {
+        match (&self.rrule, self.interval_secs) {
+            (Some(rrule), _) => rrule.replace('\n', " "),
+            (None, Some(interval_secs)) => format!("every {}", format_duration(interval_secs)),
+            (None, None) if self.is_manual() => MANUAL_SCHEDULE.to_string(),
+            (None, None) => self.cron.clone(),
+        }
+    }
+
+    /// Whether the job has no schedule of its own and only fires as a follow-up
+    pub fn is_manual(&self) -> bool {
+        self.cron.is_empty() && self.rrule.is_none() && self.interval_secs.is_none()
+    }
+
+    /// Whether the job is driven by its own task rather than a scheduler cron job
+    pub fn needs_task(&self) -> bool {
+        self.rrule.is_some() || self.interval_secs.is_some() || self.jitter_secs.is_some()
+    }
+
+    /// The random delay applied to each occurrence, if any
+    pub fn jitter(&self) -> Option<Jitter> {
+        self.jitter_secs.map(|window_secs| Jitter {
+            window_secs,
+            seed: self.jitter_seed,
+        })
+    }
+
+    /// Validate the optional active period, run limit, interval and timeout
+    pub fn validate_limits(&self) -> Result<(), String> {
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at) {
+            if ends_at <= starts_at {
+                return Err("End date must be after start date".to_string());
+            }
+        }
+        if let Some(interval_secs) = self.interval_secs {
+            if interval_secs < 1 {
+                return Err("Interval must be at least 1 second".to_string());
+            }
+        }
+        if let Some(max_runs) = self.max_runs {
+            if max_runs < 1 {
+                return Err(format!("Max runs must be at least 1, got {}", max_runs));
+            }
+        }
+        if self.timeout_secs.is_some_and(|timeout| timeout < 0) {
+            return Err("Timeout cannot be negative".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `sound` refers to a file rather than a sound theme name
+    pub fn has_sound_file(&self) -> bool {
+        self.sound.as_deref().is_some_and(is_file_reference)
+    }
+
+    /// Whether the job's active period has begun at `now` (UTC)
+    pub fn has_started(&self, now: NaiveDateTime) -> bool {
+        self.starts_at.is_none_or(|starts_at| now >= starts_at)
+    }
+
+    /// Whether the job has passed its end date or used up its runs
+    pub fn is_exhausted(&self, run_count: i64, now: NaiveDateTime) -> bool {
+        self.ends_at.is_some_and(|ends_at| now > ends_at)
+            || self.max_runs.is_some_and(|max_runs| run_count >= max_runs)
+    }
+}
+
+/// Whether an icon or sound value is a path rather than a theme name
+pub fn is_file_reference(value: &str) -> bool {
+    value.contains('/') || value.starts_with("file://")
+}
+
+/// Make icon and sound file paths absolute, the service does not share our
+/// working directory. Theme names are kept as they are
+pub fn resolve_file_reference(value: &str) -> Result<String, String> {
+    if !is_file_reference(value) || value.starts_with("file://") {
+        return Ok(value.to_string());
+    }
+    let path =
+        std::fs::canonicalize(value).map_err(|err| format!("Cannot use '{}': {}", value, err))?;
+    Ok(path.display().to_string())
+}
+
+/// Parse a notification timeout, "never" meaning the notification does not expire
+pub fn parse_timeout(value: &str) -> Result<Option<i64>, String> {
+    if value.eq_ignore_ascii_case("never") {
+        return Ok(Some(0));
+    }
+    match parse_duration(value)? {
+        0 => Err("Timeout must be at least 1 second, or \"never\"".to_string()),
+        secs => Ok(Some(secs)),
+    }
+}
+
+/// Parse an "every <duration>" schedule such as "every 45m", returning `None`
+/// when the expression is not an interval at all
+fn parse_interval(expr: &str) -> Option<Result<i64, String>> {
+    let trimmed = expr.trim();
+    let (keyword, duration) = trimmed.split_once(char::is_whitespace)?;
+    if !keyword.eq_ignore_ascii_case("every") {
+        return None;
+    }
+    Some(parse_duration(duration.trim()))
 }
 
 fn is_valid_cron(cron: &str) -> bool {
@@ -0,0 +1,98 @@
+use super::journal;
+use super::models::Timer;
+use chrono::{Duration, Utc};
+use sqlx::sqlite::SqlitePool;
+
+/// Create the countdown timer table
+pub(super) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    // Timers live outside the poke table and are deleted once they fire
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS timer (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message TEXT NOT NULL,
+            created TIMESTAMP NOT NULL,
+            due_at TIMESTAMP NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Start a timer that fires `secs` seconds from now
+pub async fn add_timer(
+    pool: &SqlitePool,
+    message: &str,
+    secs: i64,
+) -> Result<Timer, Box<dyn std::error::Error>> {
+    if secs < 1 {
+        return Err("Timer duration must be at least 1 second".into());
+    }
+
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query("INSERT INTO timer (message, created, due_at) VALUES (?, ?, ?)")
+        .bind(message)
+        .bind(now)
+        .bind(now + Duration::seconds(secs))
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+    journal::record_irreversible(&mut tx, &format!("start timer {}", id)).await?;
+    tx.commit().await?;
+
+    let timer = sqlx::query_as::<_, Timer>("SELECT * FROM timer WHERE id = ?")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    Ok(timer)
+}
+
+/// Pending timers, soonest first
+pub async fn list_timers(pool: &SqlitePool) -> Result<Vec<Timer>, sqlx::Error> {
+    sqlx::query_as::<_, Timer>("SELECT * FROM timer ORDER BY due_at, id")
+        .fetch_all(pool)
+        .await
+}
+
+/// Cancel a pending timer
+pub async fn cancel_timer(pool: &SqlitePool, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    if !take_timer(pool, id).await? {
+        return Err(format!("Timer {} not found", id).into());
+    }
+    journal::record_irreversible(&mut *pool.acquire().await?, &format!("cancel timer {}", id))
+        .await?;
+    Ok(())
+}
+
+/// Remove a timer, returning whether it was still pending. Whoever removes
+/// the timer is the one to fire it, so it fires once even if both the service
+/// and a foreground CLI are waiting on it
+pub async fn take_timer(pool: &SqlitePool, id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM timer WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Take the timers that are due, removing them
+pub async fn take_due_timers(pool: &SqlitePool) -> Result<Vec<Timer>, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+    let timers =
+        sqlx::query_as::<_, Timer>("SELECT * FROM timer WHERE due_at <= ? ORDER BY due_at")
+            .bind(now)
+            .fetch_all(&mut *tx)
+            .await?;
+    sqlx::query("DELETE FROM timer WHERE due_at <= ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(timers)
+}
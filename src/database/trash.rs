@@ -0,0 +1,72 @@
+use super::delete_poke;
+use super::journal::{self, Snapshot};
+use super::models::TrashedPoke;
+use chrono::NaiveDateTime;
+use sqlx::sqlite::SqlitePool;
+
+/// Jobs in the trash, most recently removed first
+pub async fn list_trash(pool: &SqlitePool) -> Result<Vec<TrashedPoke>, sqlx::Error> {
+    sqlx::query_as::<_, TrashedPoke>(
+        "SELECT * FROM poke WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn trashed_id(pool: &SqlitePool, name: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    let id: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM poke WHERE name = ? AND deleted_at IS NOT NULL")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    id.ok_or_else(|| format!("No job named '{}' in the trash", name).into())
+}
+
+/// Take a job out of the trash, with its calendars, follow-ups and history
+pub async fn restore_poke(pool: &SqlitePool, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let id = trashed_id(pool, name).await?;
+
+    let mut tx = pool.begin().await?;
+    let snapshot = Snapshot::of(&mut tx, id).await?;
+    sqlx::query("UPDATE poke SET deleted_at = NULL WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    journal::record(&mut tx, &format!("restore '{}'", name), &[snapshot]).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Permanently delete a job from the trash. This cannot be undone
+pub async fn purge_poke(pool: &SqlitePool, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let id = trashed_id(pool, name).await?;
+
+    let mut tx = pool.begin().await?;
+    delete_poke(&mut tx, id).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Permanently delete the jobs trashed before `before`, every trashed job if
+/// `None`. Returns the number of jobs deleted
+pub async fn purge_trash(
+    pool: &SqlitePool,
+    before: Option<NaiveDateTime>,
+) -> Result<usize, sqlx::Error> {
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM poke WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR deleted_at < ?1)",
+    )
+    .bind(before)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    for id in &ids {
+        delete_poke(&mut tx, *id).await?;
+    }
+    tx.commit().await?;
+
+    Ok(ids.len())
+}
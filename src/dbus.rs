@@ -0,0 +1,400 @@
+use crate::config;
+use crate::database::calendar::get_poke_exclusions;
+use crate::database::{
+    add_poke, get_poke_by_name, list_pokes, pause_poke, remove_poke, resume_poke, Poke, PokeStatus,
+};
+use crate::notification::fire_poke;
+use crate::schedule::upcoming_runs;
+use chrono::Utc;
+use log::{error, info};
+use sqlx::SqlitePool;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Notify;
+use zbus::object_server::SignalEmitter;
+use zbus::{fdo, interface, Connection};
+
+/// Well-known name claimed on the session bus
+pub const BUS_NAME: &str = "org.pokeme.Service";
+
+/// Bus name of the running profile's service, e.g. `org.pokeme.Service.work`,
+/// so that each profile's service can be on the bus at the same time
+pub fn bus_name() -> String {
+    match &config::get().profile {
+        // Bus name elements cannot contain '-'
+        Some(profile) => format!("{}.{}", BUS_NAME, profile.replace('-', "_")),
+        None => BUS_NAME.to_string(),
+    }
+}
+
+/// Path of the service object
+pub const OBJECT_PATH: &str = "/org/pokeme/Service";
+
+/// Session bus connection of the running service, used to emit signals
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+
+/// The `org.pokeme.Service1` interface
+struct PokeService {
+    pool: SqlitePool,
+    /// Wakes the service loop so job changes reach the scheduler right away
+    changed: Arc<Notify>,
+    /// The service's runtime. zbus calls methods from its own executor, while
+    /// the database and scheduler need Tokio
+    runtime: tokio::runtime::Handle,
+}
+
+impl PokeService {
+    /// Run a method body on the service's runtime
+    async fn run<T, F>(&self, body: F) -> fdo::Result<T>
+    where
+        T: Send + 'static,
+        F: std::future::Future<Output = fdo::Result<T>> + Send + 'static,
+    {
+        self.runtime.spawn(body).await.map_err(failed)?
+    }
+}
+
+#[interface(name = "org.pokeme.Service1")]
+impl PokeService {
+    /// All jobs as (name, schedule, detail, status, next run as a Unix
+    /// timestamp or 0)
+    async fn list_pokes(&self) -> fdo::Result<Vec<(String, String, String, String, i64)>> {
+        let pool = self.pool.clone();
+        self.run(async move {
+            let pokes = list_pokes(&pool, None).await.map_err(failed)?;
+            let mut result = Vec::new();
+            for poke in pokes {
+                let next_run = next_run(&pool, &poke).await;
+                result.push((
+                    poke.name.clone(),
+                    poke.schedule_expr(),
+                    poke.detail.clone().unwrap_or_default(),
+                    poke.status.to_string(),
+                    next_run,
+                ));
+            }
+            Ok(result)
+        })
+        .await
+    }
+
+    /// Add a job, an empty detail meaning none
+    async fn add_poke(&self, name: String, schedule: String, detail: String) -> fdo::Result<()> {
+        let pool = self.pool.clone();
+        let changed = self.changed.clone();
+        self.run(async move {
+            let detail = (!detail.is_empty()).then_some(detail);
+            let poke = Poke::new(name.clone(), schedule, detail, false).map_err(invalid)?;
+            let sched = tokio_cron_scheduler::JobScheduler::new()
+                .await
+                .map_err(failed)?;
+            add_poke(&pool, poke, &sched)
+                .await
+                .map_err(|err| invalid(err.to_string()))?;
+            changed.notify_one();
+            info!("Job '{}' added over D-Bus", name);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_poke(&self, name: String) -> fdo::Result<()> {
+        let pool = self.pool.clone();
+        let changed = self.changed.clone();
+        self.run(async move {
+            remove_poke(&pool, &name)
+                .await
+                .map_err(|err| invalid(err.to_string()))?;
+            changed.notify_one();
+            info!("Job '{}' removed over D-Bus", name);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Show a job's notification now
+    async fn fire(&self, name: String) -> fdo::Result<()> {
+        let pool = self.pool.clone();
+        self.run(async move {
+            let poke = get_poke_by_name(&pool, &name)
+                .await
+                .map_err(|err| invalid(err.to_string()))?;
+            info!("Firing '{}' over D-Bus", name);
+            tokio::spawn(async move { fire_poke(&pool, &poke, Utc::now(), 0).await });
+            Ok(())
+        })
+        .await
+    }
+
+    /// Pause a job, or resume it when `paused` is false
+    async fn pause(&self, name: String, paused: bool) -> fdo::Result<()> {
+        let pool = self.pool.clone();
+        let changed = self.changed.clone();
+        self.run(async move {
+            let result = if paused {
+                pause_poke(&pool, &name).await
+            } else {
+                resume_poke(&pool, &name).await.map(|_| ())
+            };
+            result.map_err(|err| invalid(err.to_string()))?;
+            changed.notify_one();
+            Ok(())
+        })
+        .await
+    }
+
+    /// A job's notification was shown
+    #[zbus(signal)]
+    async fn poke_fired(emitter: &SignalEmitter<'_>, name: &str) -> zbus::Result<()>;
+
+    /// A job's notification was acknowledged
+    #[zbus(signal)]
+    async fn poke_acknowledged(emitter: &SignalEmitter<'_>, name: &str) -> zbus::Result<()>;
+
+    /// Jobs were added, removed or changed
+    #[zbus(signal)]
+    async fn jobs_changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+fn failed(err: impl ToString) -> fdo::Error {
+    fdo::Error::Failed(err.to_string())
+}
+
+fn invalid(err: impl ToString) -> fdo::Error {
+    fdo::Error::InvalidArgs(err.to_string())
+}
+
+async fn next_run(pool: &SqlitePool, poke: &Poke) -> i64 {
+    if poke.status != PokeStatus::Active {
+        return 0;
+    }
+    let Ok(exclusions) = get_poke_exclusions(pool, poke.id).await else {
+        return 0;
+    };
+    upcoming_runs(poke, &exclusions, Utc::now(), 1)
+        .ok()
+        .and_then(|runs| runs.first().map(|run| run.timestamp()))
+        .unwrap_or(0)
+}
+
+/// Export the service on the session bus. The bus is found through
+/// `DBUS_SESSION_BUS_ADDRESS`, so a private `dbus-daemon` works as well
+pub async fn serve(pool: SqlitePool, changed: Arc<Notify>) {
+    let service = PokeService {
+        pool,
+        changed,
+        runtime: tokio::runtime::Handle::current(),
+    };
+    let connection = async {
+        zbus::connection::Builder::session()?
+            .name(bus_name())?
+            .serve_at(OBJECT_PATH, service)?
+            .build()
+            .await
+    }
+    .await;
+    match connection {
+        Ok(connection) => {
+            info!("D-Bus interface available as {}", bus_name());
+            let _ = CONNECTION.set(connection);
+        }
+        Err(err) => error!("Failed to register on the session bus: {}", err),
+    }
+}
+
+/// Close a desktop notification shown by the service
+pub async fn close_notification(id: u32) {
+    let result = async {
+        let connection = Connection::session().await?;
+        connection
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "CloseNotification",
+                &id,
+            )
+            .await?;
+        Ok::<(), zbus::Error>(())
+    }
+    .await;
+    if let Err(err) = result {
+        error!("Failed to close notification {}: {}", id, err);
+    }
+}
+
+/// Run a signal emission if the service is on the bus
+async fn emit<F, Fut>(signal: &str, emit: F)
+where
+    F: FnOnce(SignalEmitter<'static>) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<()>>,
+{
+    let Some(connection) = CONNECTION.get() else {
+        return;
+    };
+    let result = match SignalEmitter::new(connection, OBJECT_PATH) {
+        Ok(emitter) => emit(emitter).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        error!("Failed to emit {} signal: {}", signal, err);
+    }
+}
+
+/// Emit `PokeFired` for a job
+pub async fn poke_fired(name: &str) {
+    emit("PokeFired", |emitter| async move {
+        PokeService::poke_fired(&emitter, name).await
+    })
+    .await;
+}
+
+/// Emit `PokeAcknowledged` for a job
+pub async fn poke_acknowledged(name: &str) {
+    emit("PokeAcknowledged", |emitter| async move {
+        PokeService::poke_acknowledged(&emitter, name).await
+    })
+    .await;
+}
+
+/// Emit `JobsChanged`
+pub async fn jobs_changed() {
+    emit("JobsChanged", |emitter| async move {
+        PokeService::jobs_changed(&emitter).await
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::run_migrations;
+    use futures_util::StreamExt;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+    use zbus::{MatchRule, MessageStream, Proxy};
+
+    type Listed = Vec<(String, String, String, String, i64)>;
+
+    /// Private session bus, stopped when dropped
+    struct Bus(Child);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_bus() -> (Bus, String) {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--print-address", "--nofork"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to start dbus-daemon");
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        (Bus(child), address.trim().to_string())
+    }
+
+    /// Wait for the next signal named `member`, returning its message
+    async fn next_signal(stream: &mut MessageStream, member: &str) -> zbus::Message {
+        let wait = async {
+            while let Some(message) = stream.next().await {
+                let message = message.unwrap();
+                if message.header().member().is_some_and(|name| name == member) {
+                    return message;
+                }
+            }
+            panic!("bus connection closed");
+        };
+        tokio::time::timeout(Duration::from_secs(10), wait)
+            .await
+            .unwrap_or_else(|_| panic!("no {} signal", member))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "starts a private dbus-daemon"]
+    async fn methods_and_signals_on_private_bus() {
+        let (_bus, address) = start_bus();
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let db = std::env::temp_dir().join(format!("poke_me-{}.db", uuid::Uuid::new_v4()));
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        // Stand-in for the service loop, which emits JobsChanged once it sees the change
+        let changed = Arc::new(Notify::new());
+        let notified = changed.clone();
+        tokio::spawn(async move {
+            loop {
+                notified.notified().await;
+                jobs_changed().await;
+            }
+        });
+        serve(pool.clone(), changed).await;
+
+        let client = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface("org.pokeme.Service1")
+            .unwrap()
+            .build();
+        let mut signals = MessageStream::for_match_rule(rule, &client, None)
+            .await
+            .unwrap();
+        let proxy = Proxy::new(&client, BUS_NAME, OBJECT_PATH, "org.pokeme.Service1")
+            .await
+            .unwrap();
+
+        proxy
+            .call::<_, _, ()>("AddPoke", &("water", "every 30m", "Drink something"))
+            .await
+            .unwrap();
+        next_signal(&mut signals, "JobsChanged").await;
+        let listed: Listed = proxy.call("ListPokes", &()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        let (name, _, detail, status, next_run) = &listed[0];
+        assert_eq!(
+            (name.as_str(), detail.as_str(), status.as_str()),
+            ("water", "Drink something", "active")
+        );
+        assert!(*next_run > Utc::now().timestamp());
+
+        proxy.call::<_, _, ()>("Fire", &("water",)).await.unwrap();
+        let fired = next_signal(&mut signals, "PokeFired").await;
+        assert_eq!(fired.body().deserialize::<String>().unwrap(), "water");
+
+        proxy
+            .call::<_, _, ()>("Pause", &("water", true))
+            .await
+            .unwrap();
+        next_signal(&mut signals, "JobsChanged").await;
+        let listed: Listed = proxy.call("ListPokes", &()).await.unwrap();
+        assert_eq!((listed[0].3.as_str(), listed[0].4), ("paused", 0));
+
+        proxy
+            .call::<_, _, ()>("RemovePoke", &("water",))
+            .await
+            .unwrap();
+        next_signal(&mut signals, "JobsChanged").await;
+        let listed: Listed = proxy.call("ListPokes", &()).await.unwrap();
+        assert!(listed.is_empty());
+
+        let missing = proxy.call::<_, _, ()>("RemovePoke", &("water",)).await;
+        assert!(missing.is_err());
+
+        pool.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", db.display(), suffix));
+        }
+    }
+}
@@ -1,4 +1,13 @@
-use crate::database::Poke;
+use crate::config::{self, Config, KEYS};
+use crate::crontab::SkippedEntry;
+use crate::database::backup::CheckReport;
+use crate::database::models::{
+    Calendar, CalendarEntry, FocusSession, FocusState, FocusTotal, FollowUp, Timer, TrashedPoke,
+};
+use crate::database::{Poke, PokeStats};
+use crate::jobfile::{changed_settings, Plan};
+use crate::schedule::{format_duration, format_local, to_utc};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 
 // Display formatting constants
 pub const NAME_WIDTH: usize = 20;
@@ -18,7 +27,7 @@ fn wrap_text(text: &str, width: usize) -> Vec<String> {
     let mut current_line = String::new();
 
     for word in text.split_whitespace() {
-        if current_line.len() + word.len() + 1 <= width {
+        if current_line.len() + word.len() < width {
             if !current_line.is_empty() {
                 current_line.push(' ');
             }
@@ -43,6 +52,7 @@ pub fn display_job(poke: &Poke) {
     let detail = poke.detail.as_deref().unwrap_or("");
     let created = poke.created.format("%Y-%m-%d %H:%M");
     let sound_status = if poke.sound_enabled { "ON" } else { "OFF" };
+    let schedule = poke.schedule_expr();
 
     // Wrap the detail text
     let detail_lines = wrap_text(detail, DETAIL_WIDTH);
@@ -51,7 +61,7 @@ pub fn display_job(poke: &Poke) {
     if let Some(first_line) = detail_lines.first() {
         println!(
             "{:<NAME_WIDTH$} {:<CRON_WIDTH$} {:<DETAIL_WIDTH$} {:<SOUND_WIDTH$} {:<CREATED_WIDTH$}",
-            poke.name, poke.cron, first_line, sound_status, created
+            poke.name, schedule, first_line, sound_status, created
         );
     }
 
@@ -68,7 +78,7 @@ pub fn display_job(poke: &Poke) {
 pub fn display_job_header() {
     println!(
         "{:<NAME_WIDTH$} {:<CRON_WIDTH$} {:<DETAIL_WIDTH$} {:<SOUND_WIDTH$} {:<CREATED_WIDTH$}",
-        "Name", "Cron", "Detail", "Sound", "Created"
+        "Name", "Schedule", "Detail", "Sound", "Created"
     );
     println!("{:-<TOTAL_WIDTH$}", "");
 }
@@ -102,10 +112,357 @@ pub fn display_jobs(pokes: &[Poke], title: &str, show_count: bool) {
 }
 
 /// Display a single job in detail format with better formatting
-pub fn display_job_detail(poke: &Poke) {
+pub fn display_job_detail(
+    poke: &Poke,
+    stats: &PokeStats,
+    calendars: &[String],
+    next_run: Option<DateTime<Utc>>,
+) {
     println!("Job Details:");
     println!("{:=<TOTAL_WIDTH$}", "");
     display_job_header();
     display_job(poke);
     println!("{:=<TOTAL_WIDTH$}", "");
+
+    let last_fired = stats
+        .last_fired
+        .map(format_local)
+        .unwrap_or_else(|| "never".to_string());
+    let runs = match poke.max_runs {
+        Some(max_runs) => format!("{} of {}", stats.run_count, max_runs),
+        None => stats.run_count.to_string(),
+    };
+    println!("{:<16} {}", "Status:", poke.status);
+    if let Some(starts_at) = poke.starts_at {
+        println!("{:<16} {}", "Starts:", format_local(starts_at));
+    }
+    if let Some(ends_at) = poke.ends_at {
+        println!("{:<16} {}", "Ends:", format_local(ends_at));
+    }
+    println!("{:<16} {}", "Missed runs:", poke.missed_policy);
+    println!("{:<16} {}", "Urgency:", poke.urgency);
+    let icon = match &poke.icon {
+        Some(icon) => icon.clone(),
+        None => format!("{} (default)", config::get().default_icon),
+    };
+    println!("{:<16} {}", "Icon:", icon);
+    let sound = match (&poke.sound, poke.sound_enabled) {
+        (_, false) => "off".to_string(),
+        (Some(sound), true) => sound.clone(),
+        (None, true) => format!("{} (default)", config::get().default_sound),
+    };
+    println!("{:<16} {}", "Sound:", sound);
+    let timeout = match poke.timeout_secs {
+        Some(0) => "never expires".to_string(),
+        Some(secs) => format_duration(secs),
+        None => "server default".to_string(),
+    };
+    println!("{:<16} {}", "Timeout:", timeout);
+    if let Some(category) = &poke.category {
+        println!("{:<16} {}", "Category:", category);
+    }
+    if poke.interval_secs.is_some() {
+        let anchor = poke.anchor_at.unwrap_or(poke.created);
+        println!(
+            "{:<16} {} (since {})",
+            "Counts from:",
+            poke.interval_anchor,
+            format_local(anchor)
+        );
+    }
+    if let Some(jitter) = poke.jitter() {
+        println!(
+            "{:<16} up to {} (seed {})",
+            "Jitter:",
+            format_duration(jitter.window_secs),
+            jitter.seed
+        );
+    }
+    println!("{:<16} {}", "Times fired:", runs);
+    println!("{:<16} {}", "Last fired:", last_fired);
+    if let Some(last_acked) = stats.last_acked {
+        println!("{:<16} {}", "Acknowledged:", format_local(last_acked));
+    }
+    if let Some(next_run) = next_run {
+        println!("{:<16} {}", "Next run:", format_next_run(next_run));
+    }
+    if !calendars.is_empty() {
+        println!("{:<16} {}", "Skips dates in:", calendars.join(", "));
+    }
+}
+
+/// Display the jobs a job follows up and the chain of follow-ups it starts
+pub fn display_followups(
+    parents: &[FollowUp],
+    chain: &[(usize, FollowUp)],
+    pending: &[NaiveDateTime],
+) {
+    for link in parents {
+        println!(
+            "{:<16} {} ({})",
+            "Follows:",
+            link.other_name,
+            describe_followup(link)
+        );
+    }
+    if !chain.is_empty() {
+        println!("Follow-ups:");
+        for (depth, link) in chain {
+            println!(
+                "{:<16} {}-> {} ({})",
+                "",
+                "   ".repeat(depth - 1),
+                link.other_name,
+                describe_followup(link)
+            );
+        }
+    }
+    for due_at in pending {
+        println!("{:<16} {}", "Queued for:", format_next_run(to_utc(*due_at)));
+    }
+}
+
+/// Describe when a follow-up fires, e.g. "30m after fire"
+fn describe_followup(link: &FollowUp) -> String {
+    format!(
+        "{} after {}",
+        format_duration(link.delay_secs),
+        link.trigger
+    )
+}
+
+/// Format an upcoming run in local time
+fn format_next_run(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%a %Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Display upcoming runs in chronological order
+pub fn display_upcoming(runs: &[(DateTime<Utc>, String)]) {
+    if runs.is_empty() {
+        println!("No upcoming runs");
+        return;
+    }
+
+    println!("{:<28} {:<NAME_WIDTH$}", "Next run", "Name");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for (time, name) in runs {
+        println!("{:<28} {:<NAME_WIDTH$}", format_next_run(*time), name);
+    }
+}
+
+/// Display all calendars
+pub fn display_calendars(calendars: &[Calendar]) {
+    println!("Calendars:");
+    println!("{:<NAME_WIDTH$} {:<CREATED_WIDTH$}", "Name", "Created");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for calendar in calendars {
+        println!(
+            "{:<NAME_WIDTH$} {:<CREATED_WIDTH$}",
+            calendar.name,
+            calendar.created.format("%Y-%m-%d %H:%M")
+        );
+    }
+}
+
+/// Display a calendar with its excluded dates
+pub fn display_calendar_detail(calendar: &Calendar, entries: &[CalendarEntry]) {
+    println!("Calendar '{}' ({} entries):", calendar.name, entries.len());
+    println!(
+        "{:<12} {:<12} {:<DETAIL_WIDTH$}",
+        "From", "Until", "Summary"
+    );
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for entry in entries {
+        println!(
+            "{:<12} {:<12} {:<DETAIL_WIDTH$}",
+            entry.start_date.to_string(),
+            entry.end_date.to_string(),
+            entry.summary.as_deref().unwrap_or("")
+        );
+    }
+}
+
+/// Display crontab lines or `at` jobs that were not imported
+pub fn display_skipped_entries(skipped: &[SkippedEntry]) {
+    println!("Skipped ({}):", skipped.len());
+    println!("{:<12} {:<DETAIL_WIDTH$} Reason", "Source", "Entry");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for entry in skipped {
+        println!(
+            "{:<12} {:<DETAIL_WIDTH$} {}",
+            entry.source, entry.text, entry.reason
+        );
+    }
+}
+
+/// Display the state of a focus session
+pub fn display_focus_session(session: &FocusSession, remaining: chrono::Duration) {
+    println!("{:<16} {}", "Focus session:", session.state);
+    if matches!(session.state, FocusState::Running | FocusState::Paused) {
+        println!(
+            "{:<16} {} ({} of {})",
+            "Phase:", session.phase, session.cycle, session.cycles
+        );
+        println!(
+            "{:<16} {}",
+            "Time left:",
+            format_duration(remaining.num_seconds())
+        );
+        if session.state == FocusState::Running {
+            println!(
+                "{:<16} {}",
+                "Phase ends:",
+                format_local(session.phase_ends_at)
+            );
+        }
+    }
+    println!(
+        "{:<16} {} work, {} break, {} long break",
+        "Timings:",
+        format_duration(session.work_secs),
+        format_duration(session.break_secs),
+        format_duration(session.long_break_secs)
+    );
+    println!("{:<16} {}", "Started:", format_local(session.started_at));
+    if let Some(ended_at) = session.ended_at {
+        println!("{:<16} {}", "Ended:", format_local(ended_at));
+    }
+}
+
+/// Display completed focus time per day
+pub fn display_focus_totals(totals: &[FocusTotal]) {
+    if totals.is_empty() {
+        println!("No focus time recorded yet");
+        return;
+    }
+
+    println!("{:<12} {:<8} {:<12}", "Date", "Cycles", "Focused");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for total in totals {
+        println!(
+            "{:<12} {:<8} {:<12}",
+            total.date.to_string(),
+            total.cycles,
+            format_duration(total.work_secs)
+        );
+    }
+}
+
+/// Display pending countdown timers
+pub fn display_timers(timers: &[Timer]) {
+    if timers.is_empty() {
+        println!("No timers running");
+        return;
+    }
+
+    let now = Utc::now().naive_utc();
+    println!("{:<6} {:<12} {:<20} Message", "ID", "Left", "Due");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for timer in timers {
+        println!(
+            "{:<6} {:<12} {:<20} {}",
+            timer.id,
+            format_duration(crate::timer::remaining_secs(timer, now)),
+            format_local(timer.due_at),
+            timer.message
+        );
+    }
+}
+
+/// Display the jobs in the trash with when they are purged, `trash_days`
+/// being 0 when they are kept
+pub fn display_trash(trashed: &[TrashedPoke], trash_days: u32) {
+    println!(
+        "{:<20} {:<20} {:<20} {:<20}",
+        "Name", "Schedule", "Removed", "Purged"
+    );
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for entry in trashed {
+        let purged = if trash_days == 0 {
+            "never".to_string()
+        } else {
+            format_local(entry.deleted_at + chrono::Duration::days(trash_days.into()))
+        };
+        println!(
+            "{:<20} {:<20} {:<20} {:<20}",
+            entry.poke.name,
+            entry.poke.schedule_expr(),
+            format_local(entry.deleted_at),
+            purged
+        );
+    }
+}
+
+/// Display a job's notification as it would appear if it fired now
+pub fn display_rendered(poke: &Poke, body: &str) {
+    println!("{}", poke.name);
+    println!("{:-<TOTAL_WIDTH$}", "");
+    println!("{}", body);
+}
+
+/// Display the job changes planned from a job file, one line per job and one
+/// more per changed setting
+pub fn display_plan(plan: &Plan) {
+    for poke in &plan.create {
+        println!("  + {:<NAME_WIDTH$} {}", poke.name, poke.schedule_expr());
+    }
+    for (before, after) in &plan.update {
+        println!("  ~ {}", after.name);
+        for (setting, old, new) in changed_settings(before, after) {
+            println!("      {}: {} -> {}", setting, old, new);
+        }
+    }
+    for name in &plan.delete {
+        println!("  - {}", name);
+    }
+    println!(
+        "{} to add, {} to change, {} to remove",
+        plan.create.len(),
+        plan.update.len(),
+        plan.delete.len()
+    );
+}
+
+/// Display the result of `db check`
+pub fn display_check_report(report: &CheckReport) {
+    println!("Schema version: {}", report.schema_version);
+    if report.integrity_errors.is_empty() {
+        println!("Integrity check: ok");
+    } else {
+        println!(
+            "Integrity check: {} problem(s)",
+            report.integrity_errors.len()
+        );
+        for error in &report.integrity_errors {
+            println!("  {}", error);
+        }
+    }
+    if report.orphans.is_empty() {
+        println!("Orphaned rows: none");
+    } else {
+        println!("Orphaned rows:");
+        for (description, count) in &report.orphans {
+            println!("  {:>6}  {}", count, description);
+        }
+    }
+}
+
+/// Display every configuration setting with where its value comes from
+pub fn display_config(config: &Config) {
+    if let Some(profile) = &config.profile {
+        println!("Profile: {}", profile);
+        println!();
+    }
+    println!("{:<16} {:<44} Source", "Key", "Value");
+    println!("{:-<TOTAL_WIDTH$}", "");
+    for key in KEYS {
+        println!(
+            "{:<16} {:<44} {}",
+            key,
+            config.get(key).unwrap_or_default(),
+            config.source(key)
+        );
+    }
 }
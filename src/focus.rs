@@ -0,0 +1,156 @@
+use crate::config;
+use crate::database::focus::{get_active_focus_session, save_focus_progress};
+use crate::database::models::{FocusPhase, FocusSession, FocusState};
+use crate::schedule::format_duration;
+use chrono::{Duration, NaiveDateTime, Utc};
+use log::{error, info};
+use notify_rust::Notification;
+use sqlx::SqlitePool;
+
+/// Move a running session past every phase that ended by `now`, returning
+/// the end times of the work phases completed on the way. Phases are chained
+/// from the previous phase's end, so a late check does not stretch the session
+pub fn advance(session: &mut FocusSession, now: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let mut completed_work = Vec::new();
+    while session.state == FocusState::Running && session.phase_ends_at <= now {
+        let ended_at = session.phase_ends_at;
+        match session.phase {
+            FocusPhase::Work => {
+                completed_work.push(ended_at);
+                if session.cycle < session.cycles {
+                    start_phase(session, FocusPhase::Break, session.break_secs, ended_at);
+                } else if session.long_break_secs > 0 {
+                    start_phase(
+                        session,
+                        FocusPhase::LongBreak,
+                        session.long_break_secs,
+                        ended_at,
+                    );
+                } else {
+                    finish(session, FocusState::Completed, ended_at);
+                }
+            }
+            FocusPhase::Break => {
+                session.cycle += 1;
+                start_phase(session, FocusPhase::Work, session.work_secs, ended_at);
+            }
+            FocusPhase::LongBreak => finish(session, FocusState::Completed, ended_at),
+        }
+    }
+    completed_work
+}
+
+fn start_phase(session: &mut FocusSession, phase: FocusPhase, secs: i64, at: NaiveDateTime) {
+    session.phase = phase;
+    session.phase_ends_at = at + Duration::seconds(secs);
+}
+
+/// End a session, either completed or stopped early
+pub fn finish(session: &mut FocusSession, state: FocusState, at: NaiveDateTime) {
+    session.state = state;
+    session.paused_at = None;
+    session.ended_at = Some(at);
+}
+
+/// Pause a running session, freezing the time left in the current phase
+pub fn pause(session: &mut FocusSession, now: NaiveDateTime) -> Result<(), String> {
+    if session.state != FocusState::Running {
+        return Err("The focus session is not running".to_string());
+    }
+    session.state = FocusState::Paused;
+    session.paused_at = Some(now);
+    Ok(())
+}
+
+/// Resume a paused session, shifting the current phase by the paused time
+pub fn resume(session: &mut FocusSession, now: NaiveDateTime) -> Result<(), String> {
+    let Some(paused_at) = session
+        .paused_at
+        .filter(|_| session.state == FocusState::Paused)
+    else {
+        return Err("The focus session is not paused".to_string());
+    };
+    session.phase_ends_at += now - paused_at;
+    session.state = FocusState::Running;
+    session.paused_at = None;
+    Ok(())
+}
+
+/// Time left in the current phase
+pub fn remaining(session: &FocusSession, now: NaiveDateTime) -> Duration {
+    let reference = session.paused_at.unwrap_or(now);
+    (session.phase_ends_at - reference).max(Duration::zero())
+}
+
+/// Advance the active focus session and announce phase changes. Called by
+/// the service every tick
+pub async fn tick(pool: &SqlitePool) {
+    let mut session = match get_active_focus_session(pool).await {
+        Ok(Some(session)) if session.state == FocusState::Running => session,
+        Ok(_) => return,
+        Err(err) => {
+            error!("Failed to read focus session: {}", err);
+            return;
+        }
+    };
+
+    let now = Utc::now().naive_utc();
+    let previous = (session.phase, session.cycle);
+    let completed_work = advance(&mut session, now);
+    if (session.phase, session.cycle) == previous && session.state == FocusState::Running {
+        return;
+    }
+
+    if let Err(err) = save_focus_progress(pool, &session, &completed_work).await {
+        error!("Failed to save focus session: {}", err);
+        return;
+    }
+    if session.state == FocusState::Completed {
+        info!("Focus session completed");
+    } else {
+        info!(
+            "Focus session moved to {} (cycle {} of {})",
+            session.phase, session.cycle, session.cycles
+        );
+    }
+    announce_phase(&session);
+}
+
+/// Show a notification for the session's current phase
+fn announce_phase(session: &FocusSession) {
+    let (summary, body) = match (session.state, session.phase) {
+        (FocusState::Completed, _) => (
+            "Focus session complete".to_string(),
+            format!(
+                "{} cycles of {} done",
+                session.cycles,
+                format_duration(session.work_secs)
+            ),
+        ),
+        (_, FocusPhase::Work) => (
+            format!("Focus: work ({} of {})", session.cycle, session.cycles),
+            format!("Focus for {}", format_duration(session.work_secs)),
+        ),
+        (_, FocusPhase::Break) => (
+            "Focus: short break".to_string(),
+            format!("Take {} off", format_duration(session.break_secs)),
+        ),
+        (_, FocusPhase::LongBreak) => (
+            "Focus: long break".to_string(),
+            format!("Take {} off", format_duration(session.long_break_secs)),
+        ),
+    };
+
+    let result = Notification::new()
+        .summary(&summary)
+        .body(&body)
+        .appname(&config::get().app_name)
+        .icon(&config::get().default_icon)
+        .hint(notify_rust::Hint::SoundName(
+            config::get().default_sound.clone(),
+        ))
+        .show();
+    if let Err(err) = result {
+        error!("Failed to display focus notification: {}", err);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::config;
+use crate::cron_expr::{DAY_NAMES, MONTH_NAMES};
+use crate::database::{
+    calendar::get_poke_exclusions, get_poke_stats, list_pokes, Poke, PokeStatus,
+};
+use crate::schedule::{upcoming_runs, Schedule};
+use crate::template::{context_for, render, TemplateContext};
+use chrono::{Duration, NaiveDate, Utc};
+use log::warn;
+use sqlx::SqlitePool;
+
+/// Upper bound on expanded occurrences exported per job
+const MAX_EXPORTED_OCCURRENCES: usize = 1_000;
+
+/// An all-day span read from an iCalendar VEVENT
+#[derive(Debug, Clone)]
+pub struct IcsEvent {
+    pub start: NaiveDate,
+    /// Last day covered by the event (inclusive)
+    pub end: NaiveDate,
+    pub summary: Option<String>,
+}
+
+/// Join folded content lines (RFC 5545 section 3.1)
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in content.lines() {
+        let raw = raw.trim_end_matches('\r');
+        if let Some(rest) = raw.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(rest);
+                continue;
+            }
+        }
+        lines.push(raw.to_string());
+    }
+    lines
+}
+
+/// Split a content line into its name (without parameters), parameters and value
+fn split_property(line: &str) -> Option<(String, String, String)> {
+    let (head, value) = line.split_once(':')?;
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    Some((
+        name.to_uppercase(),
+        params.to_uppercase(),
+        value.to_string(),
+    ))
+}
+
+/// Parse the date part of a DATE or DATE-TIME value, telling whether it had a time
+fn parse_ics_date(value: &str) -> Result<(NaiveDate, bool), String> {
+    let date = value.get(..8).unwrap_or(value);
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d")
+        .map_err(|_| format!("Invalid iCalendar date: {}", value))?;
+    let midnight = value.get(9..15).is_none_or(|time| time == "000000");
+    Ok((date, !midnight))
+}
+
+/// Decode a TEXT value in one pass, so that an escaped backslash is not read
+/// as the start of another escape. Line breaks become spaces
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push(' '),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Read the date spans of all events in an .ics file. End dates in iCalendar
+/// are exclusive, so an event ending at midnight does not cover that day
+pub fn parse_ics_events(content: &str) -> Result<Vec<IcsEvent>, String> {
+    let mut events = Vec::new();
+    let mut in_event = false;
+    let mut start: Option<NaiveDate> = None;
+    let mut end: Option<(NaiveDate, bool)> = None;
+    let mut summary: Option<String> = None;
+
+    for (index, line) in unfold(content).iter().enumerate() {
+        let Some((name, _params, value)) = split_property(line) else {
+            continue;
+        };
+        match (name.as_str(), value.to_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => {
+                in_event = true;
+                start = None;
+                end = None;
+                summary = None;
+            }
+            ("END", "VEVENT") if in_event => {
+                in_event = false;
+                let start = start
+                    .ok_or_else(|| format!("Event ending on line {} has no DTSTART", index + 1))?;
+                let end = match end {
+                    Some((date, true)) => date,
+                    Some((date, false)) if date > start => date - Duration::days(1),
+                    _ => start,
+                };
+                events.push(IcsEvent {
+                    start,
+                    end,
+                    summary: summary.take(),
+                });
+            }
+            ("DTSTART", _) if in_event => start = Some(parse_ics_date(&value)?.0),
+            ("DTEND", _) if in_event => end = Some(parse_ics_date(&value)?),
+            ("SUMMARY", _) if in_event => summary = Some(unescape(&value)),
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+/// An event written to an exported calendar
+#[derive(Debug, Clone)]
+pub struct ExportEvent {
+    pub uid: String,
+    /// DTSTART value, e.g. "20240105T100000Z" or floating "20240105T100000"
+    pub start: String,
+    /// Optional RRULE value (without the "RRULE:" prefix)
+    pub rrule: Option<String>,
+    pub summary: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Escape a TEXT value (RFC 5545 section 3.3.11)
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line to at most 75 octets per physical line
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for ch in line.chars() {
+        if length + ch.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(ch);
+        length += ch.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Render a complete VCALENDAR document
+pub fn render_calendar(events: &[ExportEvent], stamp: &str) -> String {
+    let mut output = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//poke_me//poke_me//EN",
+        "CALSCALE:GREGORIAN",
+    ] {
+        output.push_str(&fold(line));
+    }
+    output.push_str(&fold(&format!(
+        "X-WR-CALNAME:{}",
+        escape(&config::get().app_name)
+    )));
+
+    for event in events {
+        output.push_str(&fold("BEGIN:VEVENT"));
+        output.push_str(&fold(&format!("UID:{}", event.uid)));
+        output.push_str(&fold(&format!("DTSTAMP:{}", stamp)));
+        output.push_str(&fold(&format!("DTSTART:{}", event.start)));
+        if let Some(rrule) = &event.rrule {
+            output.push_str(&fold(&format!("RRULE:{}", rrule)));
+        }
+        output.push_str(&fold(&format!("SUMMARY:{}", escape(&event.summary))));
+        if let Some(description) = &event.description {
+            output.push_str(&fold(&format!("DESCRIPTION:{}", escape(description))));
+        }
+        if let Some(category) = &event.category {
+            output.push_str(&fold(&format!("CATEGORIES:{}", escape(category))));
+        }
+        output.push_str(&fold("END:VEVENT"));
+    }
+
+    output.push_str(&fold("END:VCALENDAR"));
+    output
+}
+
+/// Expand a cron field into explicit values, or `None` for "*". `names` are
+/// accepted for values from `min` on. Syntax that has no RRULE equivalent
+/// (L, W, #) is an error
+fn expand_cron_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> Result<Option<Vec<u32>>, ()> {
+    if field == "*" || field == "?" {
+        return Ok(None);
+    }
+    let value = |text: &str| {
+        text.parse::<u32>()
+            .ok()
+            .or_else(|| {
+                names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(text))
+                    .map(|index| index as u32 + min)
+            })
+            .ok_or(())
+    };
+    let mut values = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| ())?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return Err(());
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else {
+            let value = value(range)?;
+            (value, if item.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(());
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort();
+    values.dedup();
+    Ok(Some(values))
+}
+
+/// Translate a 6-field cron expression (evaluated in UTC) into an RRULE,
+/// when every field has a direct RRULE equivalent
+pub fn cron_to_rrule(cron: &str) -> Option<String> {
+    let fields: Vec<&str> = cron.split_whitespace().collect();
+    let [second, minute, hour, day, month, weekday] = fields[..] else {
+        return None;
+    };
+    let join = |values: Vec<u32>| {
+        values
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let all = |min: u32, max: u32| (min..=max).collect::<Vec<u32>>();
+
+    let seconds = expand_cron_field(second, 0, 59, &[])
+        .ok()?
+        .unwrap_or_else(|| all(0, 59));
+    let minutes = expand_cron_field(minute, 0, 59, &[])
+        .ok()?
+        .unwrap_or_else(|| all(0, 59));
+    let hours = expand_cron_field(hour, 0, 23, &[])
+        .ok()?
+        .unwrap_or_else(|| all(0, 23));
+    let days = expand_cron_field(day, 1, 31, &[]).ok()?;
+    let months = expand_cron_field(month, 1, 12, &MONTH_NAMES).ok()?;
+    let weekdays = expand_cron_field(weekday, 0, 7, &DAY_NAMES).ok()?;
+
+    let mut parts = vec![
+        "FREQ=DAILY".to_string(),
+        format!("BYHOUR={}", join(hours)),
+        format!("BYMINUTE={}", join(minutes)),
+        format!("BYSECOND={}", join(seconds)),
+    ];
+    if let Some(days) = days {
+        parts.push(format!("BYMONTHDAY={}", join(days)));
+    }
+    if let Some(months) = months {
+        parts.push(format!("BYMONTH={}", join(months)));
+    }
+    if let Some(weekdays) = weekdays {
+        const CODES: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+        let mut codes: Vec<&str> = Vec::new();
+        for code in weekdays.iter().map(|day| CODES[*day as usize % 7]) {
+            if !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        parts.push(format!("BYDAY={}", codes.join(",")));
+    }
+
+    Some(parts.join(";"))
+}
+
+/// A job's detail as its notification would show it, or the raw template if
+/// it does not render
+fn rendered_detail(poke: &Poke, context: &TemplateContext) -> Option<String> {
+    let detail = poke.detail.as_ref()?;
+    match render(detail, context) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            warn!("Job '{}' detail not rendered: {}", poke.name, err);
+            Some(detail.clone())
+        }
+    }
+}
+
+/// Build an iCalendar document with the upcoming runs of all active jobs.
+/// Jobs whose schedule maps cleanly to an RRULE are exported as recurring
+/// events, the others are expanded over the next `days` days
+pub async fn export_pokes(
+    pool: &SqlitePool,
+    days: i64,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let now = Utc::now();
+    let horizon = now + Duration::days(days);
+    let mut events = Vec::new();
+
+    for poke in list_pokes(pool, None).await? {
+        if poke.status != PokeStatus::Active {
+            continue;
+        }
+        let exclusions = get_poke_exclusions(pool, poke.id).await?;
+        let limited = poke.starts_at.is_some()
+            || poke.ends_at.is_some()
+            || poke.max_runs.is_some()
+            || poke.jitter_secs.is_some()
+            || !exclusions.is_empty();
+        let mut context = context_for(pool, &poke, now).await?;
+
+        // Recurring event straight from the job's own RRULE. DTSTART counts as
+        // the first instance, so it is moved to the rule's first real occurrence
+        if let (Schedule::RRule(rule), false) = (Schedule::for_poke(&poke)?, limited) {
+            if let Some(first) = rule.first_occurrence() {
+                events.push(ExportEvent {
+                    uid: format!("poke-{}@poke_me", poke.id),
+                    start: first.format("%Y%m%dT%H%M%S").to_string(),
+                    rrule: Some(rule.rule_parts()),
+                    summary: poke.name.clone(),
+                    description: rendered_detail(&poke, &context),
+                    category: poke.category.clone(),
+                });
+            }
+            continue;
+        }
+
+        // Recurring event translated from the cron expression
+        if let (None, false, Some(rrule)) = (&poke.rrule, limited, cron_to_rrule(&poke.cron)) {
+            if let Some(first) = upcoming_runs(&poke, &exclusions, now, 1)?.first() {
+                events.push(ExportEvent {
+                    uid: format!("poke-{}@poke_me", poke.id),
+                    start: first.format("%Y%m%dT%H%M%SZ").to_string(),
+                    rrule: Some(rrule),
+                    summary: poke.name.clone(),
+                    description: rendered_detail(&poke, &context),
+                    category: poke.category.clone(),
+                });
+            }
+            continue;
+        }
+
+        // Everything else is expanded occurrence by occurrence
+        let stats = get_poke_stats(pool, poke.id).await?;
+        let remaining = poke.max_runs.map_or(MAX_EXPORTED_OCCURRENCES, |max_runs| {
+            (max_runs - stats.run_count).max(0) as usize
+        });
+        let runs: Vec<_> = upcoming_runs(
+            &poke,
+            &exclusions,
+            now,
+            remaining.min(MAX_EXPORTED_OCCURRENCES + 1),
+        )?
+        .into_iter()
+        .take_while(|time| *time <= horizon)
+        .collect();
+        if runs.len() > MAX_EXPORTED_OCCURRENCES {
+            warn!(
+                "Job '{}' has too many runs, exporting only the first {}",
+                poke.name, MAX_EXPORTED_OCCURRENCES
+            );
+        }
+        // Each occurrence gets the detail it will have when it fires
+        let first_run_count = context.run_count;
+        for (index, &time) in runs.iter().take(MAX_EXPORTED_OCCURRENCES).enumerate() {
+            context.now = time;
+            context.run_count = first_run_count + index as i64;
+            context.next_run = runs.get(index + 1).copied();
+            events.push(ExportEvent {
+                uid: format!("poke-{}-{}@poke_me", poke.id, time.timestamp()),
+                start: time.format("%Y%m%dT%H%M%SZ").to_string(),
+                rrule: None,
+                summary: poke.name.clone(),
+                description: rendered_detail(&poke, &context),
+                category: poke.category.clone(),
+            });
+        }
+    }
+
+    Ok(render_calendar(
+        &events,
+        &now.format("%Y%m%dT%H%M%SZ").to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_reads_each_escape_once() {
+        assert_eq!(unescape(r"Line\nbreak"), "Line break");
+        assert_eq!(unescape(r"C:\\new"), r"C:\new");
+        assert_eq!(unescape(r"a\,b\;c\\\n"), r"a,b;c\ ");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn escape_round_trips() {
+        let text = "a\\nb, c; d";
+        assert_eq!(unescape(&escape(text)), text);
+    }
+}
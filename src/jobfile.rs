@@ -0,0 +1,391 @@
+use crate::config;
+use crate::database::models::{parse_timeout, resolve_file_reference};
+use crate::database::{
+    apply_poke_changes, config_dir, list_pokes, IntervalAnchor, MissedPolicy, Poke, PokeStatus,
+    Urgency,
+};
+use crate::schedule::{format_duration, parse_duration, parse_local_datetime, to_utc};
+use chrono::{Local, NaiveDateTime, Timelike};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio_cron_scheduler::JobScheduler;
+use toml::Spanned;
+
+/// Comment placed at the top of written job files
+const HEADER: &str = "\
+# poke_me jobs. Each [[job]] table is one job, matched to existing jobs by name.
+# Required: name, schedule (cron, @daily, RRULE, \"every 45m\" or \"manual\").
+# Optional: detail, sound, status (active, paused, completed), urgency, missed,
+# starts, ends (local \"YYYY-MM-DD HH:MM\"), max_runs, jitter, anchor, icon,
+# timeout, sound_name, category.
+";
+
+/// A job as written in a job file. Durations and dates are in the same
+/// forms the command line accepts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpec {
+    pub name: String,
+    pub schedule: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub sound: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub status: PokeStatus,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub urgency: Urgency,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub missed: MissedPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ends: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub anchor: IntervalAnchor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+#[derive(Serialize)]
+struct JobFileOut<'a> {
+    job: &'a [JobSpec],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobFileIn {
+    #[serde(default)]
+    job: Vec<Spanned<JobSpec>>,
+}
+
+/// Local time of a stored date, without seconds unless it has any
+fn format_exact(utc: NaiveDateTime) -> String {
+    let local = to_utc(utc).with_timezone(&Local);
+    if local.second() == 0 {
+        local.format("%Y-%m-%d %H:%M").to_string()
+    } else {
+        local.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+}
+
+impl JobSpec {
+    pub fn from_poke(poke: &Poke) -> Self {
+        JobSpec {
+            name: poke.name.clone(),
+            schedule: poke.schedule_expr(),
+            detail: poke.detail.clone(),
+            sound: poke.sound_enabled,
+            status: poke.status,
+            urgency: poke.urgency,
+            missed: poke.missed_policy,
+            starts: poke.starts_at.map(format_exact),
+            ends: poke.ends_at.map(format_exact),
+            max_runs: poke.max_runs,
+            jitter: poke.jitter_secs.map(format_duration),
+            anchor: poke.interval_anchor,
+            icon: poke.icon.clone(),
+            timeout: poke.timeout_secs.map(|secs| match secs {
+                0 => "never".to_string(),
+                secs => format_duration(secs),
+            }),
+            sound_name: poke.sound.clone(),
+            category: poke.category.clone(),
+        }
+    }
+
+    /// Build the job described by this entry. Settings the file does not
+    /// cover, such as the jitter seed, are kept from `existing`
+    pub fn to_poke(&self, existing: Option<&Poke>) -> Result<Poke, String> {
+        if self.name.trim().is_empty() {
+            return Err("Name cannot be empty".to_string());
+        }
+        let mut poke = match existing {
+            Some(existing) => {
+                let mut poke = existing.clone();
+                poke.set_schedule(&self.schedule)?;
+                poke.detail = self.detail.clone();
+                poke.sound_enabled = self.sound;
+                poke
+            }
+            None => Poke::new(
+                self.name.clone(),
+                self.schedule.clone(),
+                self.detail.clone(),
+                self.sound,
+            )?,
+        };
+        poke.status = self.status;
+        poke.urgency = self.urgency;
+        poke.missed_policy = self.missed;
+        poke.starts_at = self
+            .starts
+            .as_deref()
+            .map(|value| parse_local_datetime(value, false))
+            .transpose()?;
+        poke.ends_at = self
+            .ends
+            .as_deref()
+            .map(|value| parse_local_datetime(value, true))
+            .transpose()?;
+        poke.max_runs = self.max_runs;
+        poke.jitter_secs = self.jitter.as_deref().map(parse_duration).transpose()?;
+        poke.interval_anchor = self.anchor;
+        // Paths already stored are kept even if the file has gone away since
+        let resolve = |value: &Option<String>, current: Option<&Option<String>>| match value {
+            Some(value) if current != Some(&Some(value.clone())) => {
+                resolve_file_reference(value).map(Some)
+            }
+            _ => Ok(value.clone()),
+        };
+        poke.icon = resolve(&self.icon, existing.map(|poke| &poke.icon))?;
+        poke.timeout_secs = self
+            .timeout
+            .as_deref()
+            .map(parse_timeout)
+            .transpose()?
+            .flatten();
+        poke.sound = resolve(&self.sound_name, existing.map(|poke| &poke.sound))?;
+        poke.category = self.category.clone();
+        Ok(poke)
+    }
+}
+
+/// Write jobs as a TOML document
+pub fn to_document(pokes: &[Poke]) -> Result<String, Box<dyn std::error::Error>> {
+    let jobs: Vec<JobSpec> = pokes.iter().map(JobSpec::from_poke).collect();
+    let body = toml::to_string_pretty(&JobFileOut { job: &jobs })?;
+    Ok(format!("{}\n{}", HEADER, body))
+}
+
+/// A problem in a job file, with the line it was found on when known
+#[derive(Debug)]
+pub struct DocumentError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Changes needed to make the stored jobs match a job file
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub create: Vec<Poke>,
+    /// Changed jobs, as stored and as they will be
+    pub update: Vec<(Poke, Poke)>,
+    pub delete: Vec<String>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+
+    /// Write all changes in a single transaction
+    pub async fn apply(
+        &self,
+        pool: &SqlitePool,
+        sched: &JobScheduler,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let update: Vec<Poke> = self.update.iter().map(|(_, poke)| poke.clone()).collect();
+        apply_poke_changes(pool, &self.create, &update, &self.delete, sched).await
+    }
+}
+
+/// Settings that differ between two versions of a job, as (setting, old, new)
+/// in job file notation
+pub fn changed_settings(before: &Poke, after: &Poke) -> Vec<(String, String, String)> {
+    let table = |poke: &Poke| toml::Table::try_from(JobSpec::from_poke(poke)).unwrap_or_default();
+    let (before, after) = (table(before), table(after));
+    let show = |value: Option<&toml::Value>| value.map_or("(none)".to_string(), |v| v.to_string());
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| (key.clone(), show(before.get(key)), show(after.get(key))))
+        .collect()
+}
+
+/// Compare a job file with the stored jobs. Jobs named in `deletable` that
+/// the file no longer contains are deleted, other missing jobs are left alone.
+/// All problems are returned at once, with their line numbers
+pub fn plan(
+    text: &str,
+    existing: &[Poke],
+    deletable: &[String],
+) -> Result<Plan, Vec<DocumentError>> {
+    let file: JobFileIn = toml::from_str(text).map_err(|err| {
+        vec![DocumentError {
+            line: err.span().map(|span| line_of(text, span.start)),
+            message: err.message().to_string(),
+        }]
+    })?;
+
+    let by_name: HashMap<&str, &Poke> = existing
+        .iter()
+        .map(|poke| (poke.name.as_str(), poke))
+        .collect();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut plan = Plan::default();
+    let mut errors = Vec::new();
+
+    for entry in &file.job {
+        let line = line_of(text, entry.span().start);
+        let spec = entry.get_ref();
+        if let Some(first) = seen.insert(spec.name.clone(), line) {
+            errors.push(DocumentError {
+                line: Some(line),
+                message: format!("Job '{}' is already defined on line {}", spec.name, first),
+            });
+            continue;
+        }
+        let current = by_name.get(spec.name.as_str()).copied();
+        match spec.to_poke(current) {
+            Ok(poke) => match current {
+                Some(current) if poke == *current => {}
+                Some(current) => plan.update.push((current.clone(), poke)),
+                None => plan.create.push(poke),
+            },
+            Err(err) => errors.push(DocumentError {
+                line: Some(line),
+                message: format!("Job '{}': {}", spec.name, err),
+            }),
+        }
+    }
+
+    plan.delete = deletable
+        .iter()
+        .filter(|name| by_name.contains_key(name.as_str()) && !seen.contains_key(*name))
+        .cloned()
+        .collect();
+
+    if errors.is_empty() {
+        Ok(plan)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Where `apply` looks for the job file when none is given, one per profile
+pub fn default_path() -> PathBuf {
+    match &config::get().profile {
+        Some(profile) => config_dir().join(format!("pokes.{}.toml", profile)),
+        None => config_dir().join("pokes.toml"),
+    }
+}
+
+/// Read a job file and compare it with the stored jobs. With `prune`, jobs
+/// missing from the file are deleted
+pub async fn plan_file(
+    pool: &SqlitePool,
+    path: &Path,
+    prune: bool,
+) -> Result<Plan, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let existing = list_pokes(pool, None).await?;
+    let deletable: Vec<String> = if prune {
+        existing.iter().map(|poke| poke.name.clone()).collect()
+    } else {
+        Vec::new()
+    };
+    plan(&text, &existing, &deletable).map_err(|errors| {
+        errors
+            .iter()
+            .map(|error| format!("{}: {}", path.display(), error))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into()
+    })
+}
+
+/// Applies a job file whenever it changes, for the service
+pub struct FileWatch {
+    path: PathBuf,
+    prune: bool,
+    modified: Option<SystemTime>,
+    missing_reported: bool,
+}
+
+impl FileWatch {
+    pub fn new(path: PathBuf, prune: bool) -> Self {
+        FileWatch {
+            path,
+            prune,
+            modified: None,
+            missing_reported: false,
+        }
+    }
+
+    /// Apply the file if it was modified since the last check. Invalid files
+    /// are reported and leave the jobs as they are
+    pub async fn check(&mut self, pool: &SqlitePool, sched: &JobScheduler) {
+        let modified = match std::fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(err) => {
+                if !self.missing_reported {
+                    warn!("Cannot watch job file {}: {}", self.path.display(), err);
+                    self.missing_reported = true;
+                }
+                self.modified = None;
+                return;
+            }
+        };
+        self.missing_reported = false;
+        if self.modified == Some(modified) {
+            return;
+        }
+        self.modified = Some(modified);
+
+        let result = async {
+            let plan = plan_file(pool, &self.path, self.prune).await?;
+            if !plan.is_empty() {
+                plan.apply(pool, sched).await?;
+            }
+            Ok::<_, Box<dyn std::error::Error>>(plan)
+        }
+        .await;
+        match result {
+            Ok(plan) if plan.is_empty() => {}
+            Ok(plan) => info!(
+                "Applied {}: {} added, {} updated, {} removed",
+                self.path.display(),
+                plan.create.len(),
+                plan.update.len(),
+                plan.delete.len()
+            ),
+            Err(err) => error!("Job file not applied: {}", err),
+        }
+    }
+}
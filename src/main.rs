@@ -1,12 +1,28 @@
+mod api;
 mod cli;
+mod config;
+mod cron_expr;
+mod crontab;
 mod database;
+mod dbus;
 mod display;
+mod focus;
+mod ics;
+mod jobfile;
 mod notification;
+mod rrule;
+mod schedule;
 mod service;
+mod template;
+mod timer;
+mod tui;
+mod wizard;
 
 use clap::Parser;
-use cli::{handle_commands, Cli};
+use cli::{handle_commands, handle_config_command, handle_db_command, Cli};
+use jobfile::FileWatch;
 use service::run_service;
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,9 +34,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
+    // An invalid configuration must not prevent `config` from fixing it
+    let overrides = cli.overrides();
+    if let Err(err) = config::init(&overrides) {
+        if !matches!(cli.command, cli::Commands::Config { .. }) {
+            println!("ERROR: {}", err);
+            std::process::exit(1);
+        }
+    }
+
     match cli.command {
-        cli::Commands::Service { daemon } => {
-            run_service(daemon).await?;
+        cli::Commands::Config { action } => {
+            if let Err(err) = handle_config_command(action, &overrides) {
+                println!("ERROR: {}", err);
+            }
+        }
+        cli::Commands::Db { action } => {
+            if let Err(err) = handle_db_command(action).await {
+                println!("ERROR: {}", err);
+            }
+        }
+        cli::Commands::Service {
+            daemon,
+            api,
+            api_port,
+            dbus,
+            watch,
+            prune,
+        } => {
+            // Made absolute, the daemon does not keep our working directory
+            let watch = watch
+                .map(|file| file.map_or_else(jobfile::default_path, PathBuf::from))
+                .map(|path| std::path::absolute(&path).map(|path| FileWatch::new(path, prune)))
+                .transpose()?;
+            let api_port = api.then(|| api_port.unwrap_or(config::get().api_port));
+            run_service(daemon, api_port, dbus, watch).await?;
         }
         command => {
             // Handle all other commands
@@ -1,51 +1,354 @@
-use crate::database::Poke;
+use crate::config;
+use crate::cron_expr::scheduler_cron;
+use crate::database::{
+    acknowledge_poke,
+    calendar::get_poke_exclusions,
+    followup::{has_ack_followups, queue_followups},
+    get_poke_stats, record_fire, set_poke_status, FollowUpTrigger, IntervalAnchor, Poke,
+    PokeStatus, Urgency,
+};
+use crate::dbus;
+use crate::schedule::Schedule;
+use crate::template::render_detail;
+use chrono::{DateTime, Duration, Utc};
 use log::{debug, error, info};
 use notify_rust::Notification;
+use sqlx::SqlitePool;
+use tokio::task::JoinHandle;
 use tokio_cron_scheduler::{Job, JobScheduler};
+use uuid::Uuid;
 
+/// How late a scheduler tick may be before it is treated as missed. Ticks
+/// delivered after a suspend are left to the service's catch-up logic
+pub const LATE_TICK_GRACE_SECS: i64 = 60;
+
+/// Longest single sleep of a task-driven job, so that wall-clock jumps
+/// (suspend, clock changes) are noticed reasonably quickly
+const MAX_TASK_SLEEP_SECS: i64 = 30;
+
+/// Longest wait for an acknowledgement when the job has no next occurrence
+const MAX_ACK_WAIT_SECS: i64 = 24 * 60 * 60;
+
+/// Handle to a scheduled job, needed to unschedule it
+pub enum JobHandle {
+    /// Cron job registered in tokio-cron-scheduler
+    Cron(Uuid),
+    /// Job driven by its own task, for schedules the cron scheduler cannot express
+    Task(JoinHandle<()>),
+    /// Job without a schedule, only fired as a follow-up
+    Manual,
+}
+
+impl JobHandle {
+    /// Stop the job from firing again
+    pub async fn remove(self, sched: &JobScheduler) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            JobHandle::Cron(job_id) => sched.remove(&job_id).await?,
+            JobHandle::Task(task) => task.abort(),
+            JobHandle::Manual => {}
+        }
+        Ok(())
+    }
+}
+
+/// Schedule a job, returning a handle to unschedule it later
 pub async fn setup_notification(
+    pool: &SqlitePool,
     poke: &Poke,
     sched: &JobScheduler,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<JobHandle, Box<dyn std::error::Error>> {
+    if poke.is_manual() {
+        debug!(
+            "Job '{}' has no schedule, it only fires as a follow-up",
+            poke.name
+        );
+        return Ok(JobHandle::Manual);
+    }
+
+    // RRULEs, intervals and jittered times cannot be expressed as scheduler cron jobs
+    if poke.needs_task() {
+        return setup_task_notification(pool, poke);
+    }
+
     debug!(
         "Setting up notification for job: '{}' with cron: '{}'",
         poke.name, poke.cron
     );
 
     // Clone the data needed for the notification
-    let name = poke.name.clone();
-    let detail = poke.detail.clone();
-    let sound_enabled = poke.sound_enabled;
+    let pool = pool.clone();
+    let job_poke = poke.clone();
 
     // Setup notifications for the new job
-    let job = Job::new(poke.cron.clone(), move |_uuid, _l| {
-        info!("Job '{}' executing notification", name);
-
-        let mut notification = Notification::new();
-        notification
-            .summary(&name)
-            .body(detail.as_deref().unwrap_or(""))
-            .appname("Poke Me")
-            .icon("clock")
-            .hint(notify_rust::Hint::Urgency(notify_rust::Urgency::Normal));
-
-        // Only add sound if enabled for this job
-        if sound_enabled {
-            notification.hint(notify_rust::Hint::SoundName("message-new-instant".into()));
-        }
-
-        let result = notification.show();
-        match result {
-            Ok(_) => info!("Notification for '{}' displayed successfully", name),
-            Err(e) => error!("Failed to display notification for '{}': {}", name, e),
-        }
+    // The scheduler has no year field; off-year ticks are dropped by `is_tick_on_time`
+    let job = Job::new_async(scheduler_cron(&poke.cron).as_str(), move |_uuid, _l| {
+        let pool = pool.clone();
+        let poke = job_poke.clone();
+        Box::pin(async move {
+            let Some(occurrence) = tick_occurrence(&poke) else {
+                info!(
+                    "Job '{}' tick arrived late or outside its years, skipping",
+                    poke.name
+                );
+                return;
+            };
+            fire_poke(&pool, &poke, occurrence, 0).await;
+        })
     })?;
 
     debug!(
         "Job '{}' created successfully, adding to scheduler",
         poke.name
     );
-    sched.add(job).await?;
+    let job_id = sched.add(job).await?;
     debug!("Job '{}' added to scheduler successfully", poke.name);
-    Ok(())
+    Ok(JobHandle::Cron(job_id))
+}
+
+/// Drive a job from its own task, sleeping until each occurrence
+fn setup_task_notification(
+    pool: &SqlitePool,
+    poke: &Poke,
+) -> Result<JobHandle, Box<dyn std::error::Error>> {
+    debug!(
+        "Setting up notification for job: '{}' with schedule: '{}'",
+        poke.name,
+        poke.schedule_expr()
+    );
+
+    // Validate up front so errors surface to the caller rather than the task
+    Schedule::for_poke(poke)?;
+
+    let pool = pool.clone();
+    let poke = poke.clone();
+    let task = tokio::spawn(async move {
+        let Ok(schedule) = Schedule::for_poke(&poke) else {
+            return;
+        };
+        let mut after = Utc::now();
+        loop {
+            let Some(next) = schedule.iter_after(after).next() else {
+                info!("Job '{}' has no further occurrences", poke.name);
+                return;
+            };
+
+            // Sleep in bounded steps so a suspend does not delay the check
+            loop {
+                let remaining = next - Utc::now();
+                if remaining <= Duration::zero() {
+                    break;
+                }
+                let step = remaining.min(Duration::seconds(MAX_TASK_SLEEP_SECS));
+                tokio::time::sleep(step.to_std().unwrap_or_default()).await;
+            }
+
+            // Occurrences passed during a suspend are left to catch-up
+            if Utc::now() - next <= Duration::seconds(LATE_TICK_GRACE_SECS) {
+                fire_poke(&pool, &poke, next, 0).await;
+            } else {
+                info!(
+                    "Job '{}' occurrence arrived late, leaving it to catch-up",
+                    poke.name
+                );
+            }
+            after = next.max(Utc::now() - Duration::seconds(LATE_TICK_GRACE_SECS));
+        }
+    });
+
+    Ok(JobHandle::Task(task))
+}
+
+/// The occurrence of the job within the grace period before now that a
+/// scheduler tick stands for, if any. Late ticks are left to the service's
+/// catch-up logic
+fn tick_occurrence(poke: &Poke) -> Option<DateTime<Utc>> {
+    let window_start = Utc::now() - Duration::seconds(LATE_TICK_GRACE_SECS);
+    match Schedule::for_poke(poke) {
+        Ok(schedule) => schedule
+            .iter_after(window_start)
+            .next()
+            .filter(|time| *time <= Utc::now()),
+        Err(_) => Some(Utc::now()),
+    }
+}
+
+/// Show the notification for a job and record it in the history.
+/// `occurrence` is the scheduled time being fired, checked against the job's
+/// exclusion calendars, and `missed` the number of missed occurrences this
+/// firing summarises
+pub async fn fire_poke(pool: &SqlitePool, poke: &Poke, occurrence: DateTime<Utc>, missed: i64) {
+    let now = Utc::now().naive_utc();
+    if !poke.has_started(now) {
+        debug!("Job '{}' has not started yet, skipping", poke.name);
+        return;
+    }
+    let run_count = match get_poke_stats(pool, poke.id).await {
+        Ok(stats) => stats.run_count,
+        Err(err) => {
+            error!("Failed to read history for '{}': {}", poke.name, err);
+            0
+        }
+    };
+    if poke.is_exhausted(run_count, now) {
+        complete_poke(pool, poke).await;
+        return;
+    }
+
+    match get_poke_exclusions(pool, poke.id).await {
+        Ok(exclusions) if exclusions.contains(occurrence) => {
+            info!(
+                "Job '{}' is excluded on {} by its calendars, skipping",
+                poke.name,
+                occurrence.with_timezone(&chrono::Local).format("%Y-%m-%d")
+            );
+            return;
+        }
+        Ok(_) => {}
+        Err(err) => error!("Failed to read calendars for '{}': {}", poke.name, err),
+    }
+
+    info!("Job '{}' executing notification", poke.name);
+
+    let mut body = match render_detail(pool, poke).await {
+        Ok(body) => body,
+        Err(err) => {
+            error!("Failed to render detail of '{}': {}", poke.name, err);
+            poke.detail.clone().unwrap_or_default()
+        }
+    };
+    if missed > 1 {
+        if !body.is_empty() {
+            body.push_str("\n\n");
+        }
+        body.push_str(&format!("Missed {} times", missed));
+    }
+
+    let mut notification = Notification::new();
+    notification
+        .summary(&poke.name)
+        .body(&body)
+        .appname(&config::get().app_name)
+        .icon(poke.icon.as_deref().unwrap_or(&config::get().default_icon))
+        .hint(notify_rust::Hint::Urgency(match poke.urgency {
+            Urgency::Low => notify_rust::Urgency::Low,
+            Urgency::Normal => notify_rust::Urgency::Normal,
+            Urgency::Critical => notify_rust::Urgency::Critical,
+        }));
+    match poke.timeout_secs {
+        Some(0) => {
+            notification.timeout(notify_rust::Timeout::Never);
+        }
+        Some(secs) => {
+            notification.timeout(notify_rust::Timeout::Milliseconds(
+                (secs * 1000).min(u32::MAX as i64) as u32,
+            ));
+        }
+        None => {}
+    }
+    if let Some(category) = &poke.category {
+        notification.hint(notify_rust::Hint::Category(category.clone()));
+    }
+
+    // Only add sound if enabled for this job
+    if poke.sound_enabled {
+        let sound = poke
+            .sound
+            .as_deref()
+            .unwrap_or(&config::get().default_sound);
+        notification.hint(if poke.has_sound_file() {
+            notify_rust::Hint::SoundFile(sound.trim_start_matches("file://").into())
+        } else {
+            notify_rust::Hint::SoundName(sound.into())
+        });
+    }
+
+    // Offer an acknowledge action when it restarts the job's interval or starts follow-ups
+    let wants_ack = (poke.interval_secs.is_some() && poke.interval_anchor == IntervalAnchor::Ack)
+        || has_ack_followups(pool, poke.id).await.unwrap_or(false);
+    if wants_ack {
+        notification
+            .action("default", "Acknowledge")
+            .action("acknowledge", "Done");
+    }
+
+    let result = notification.show();
+
+    if let Err(err) = record_fire(pool, poke.id, now, missed).await {
+        error!("Failed to record firing of '{}': {}", poke.name, err);
+    }
+    if let Err(err) = queue_followups(pool, poke.id, FollowUpTrigger::Fire, now).await {
+        error!("Failed to queue follow-ups of '{}': {}", poke.name, err);
+    }
+    dbus::poke_fired(&poke.name).await;
+
+    match result {
+        Ok(handle) => {
+            info!("Notification for '{}' displayed successfully", poke.name);
+            if wants_ack {
+                wait_for_ack(pool, poke, handle);
+            }
+        }
+        Err(e) => error!("Failed to display notification for '{}': {}", poke.name, e),
+    }
+
+    // Retire the job right away when this was its last run
+    if poke.is_exhausted(run_count + 1, now) {
+        complete_poke(pool, poke).await;
+    }
+}
+
+/// Record the acknowledgement once the user acts on the notification. The
+/// wait ends at the job's next occurrence, which shows a new notification,
+/// and the unanswered one is closed then
+fn wait_for_ack(pool: &SqlitePool, poke: &Poke, handle: notify_rust::NotificationHandle) {
+    let pool = pool.clone();
+    let poke = poke.clone();
+    let id = handle.id();
+    let now = Utc::now();
+    let limit = now + Duration::seconds(MAX_ACK_WAIT_SECS);
+    let deadline = Schedule::for_poke(&poke)
+        .ok()
+        .and_then(|schedule| schedule.iter_after(now).next())
+        .map_or(limit, |next| next.min(limit));
+    tokio::spawn(async move {
+        let wait = tokio::task::spawn_blocking(move || {
+            let mut acked = false;
+            handle.wait_for_action(|action| {
+                acked = action == "default" || action == "acknowledge";
+            });
+            acked
+        });
+        let timeout = (deadline - Utc::now()).to_std().unwrap_or_default();
+        let acked = match tokio::time::timeout(timeout, wait).await {
+            Ok(result) => result.unwrap_or(false),
+            Err(_) => {
+                info!(
+                    "Job '{}' was not acknowledged before its next occurrence",
+                    poke.name
+                );
+                // Closing the notification also ends the blocked wait
+                dbus::close_notification(id).await;
+                false
+            }
+        };
+
+        if acked {
+            info!("Job '{}' acknowledged", poke.name);
+            if let Err(err) = acknowledge_poke(&pool, &poke, Utc::now().naive_utc()).await {
+                error!(
+                    "Failed to record acknowledgement of '{}': {}",
+                    poke.name, err
+                );
+            }
+            dbus::poke_acknowledged(&poke.name).await;
+        }
+    });
+}
+
+/// Mark a job as completed so the service stops scheduling it
+async fn complete_poke(pool: &SqlitePool, poke: &Poke) {
+    info!("Job '{}' is exhausted, marking it completed", poke.name);
+    if let Err(err) = set_poke_status(pool, poke.id, PokeStatus::Completed).await {
+        error!("Failed to complete job '{}': {}", poke.name, err);
+    }
 }
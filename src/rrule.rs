@@ -0,0 +1,826 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+    Utc, Weekday,
+};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Periods without a single occurrence after which iteration gives up
+const MAX_EMPTY_PERIODS: u32 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+}
+
+/// A recurrence rule (RFC 5545 section 3.3.10) anchored at a local `DTSTART`.
+///
+/// Supported parts: FREQ, INTERVAL, COUNT, UNTIL, BYMONTH, BYMONTHDAY, BYDAY,
+/// BYHOUR, BYMINUTE, BYSECOND, BYSETPOS and WKST. Times are floating, i.e.
+/// interpreted in the local timezone
+#[derive(Debug, Clone, PartialEq)]
+pub struct RRule {
+    pub dtstart: NaiveDateTime,
+    pub freq: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDateTime>,
+    pub by_month: Vec<u32>,
+    pub by_month_day: Vec<i32>,
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_hour: Vec<u32>,
+    pub by_minute: Vec<u32>,
+    pub by_second: Vec<u32>,
+    pub by_set_pos: Vec<i32>,
+    pub week_start: Weekday,
+}
+
+/// Whether a schedule expression is an RRULE rather than a cron expression
+pub fn is_rrule(expr: &str) -> bool {
+    let upper = expr.trim_start().to_uppercase();
+    upper.starts_with("FREQ=") || upper.starts_with("RRULE:") || upper.starts_with("DTSTART")
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// Parse a DATE or DATE-TIME value, converting UTC ("Z") times to local time
+fn parse_datetime_value(value: &str) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map_err(|_| format!("Invalid date-time: {}", value))?;
+        return Ok(Utc
+            .from_utc_datetime(&naive)
+            .with_timezone(&Local)
+            .naive_local());
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Ok(naive);
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map(|date| date.and_time(NaiveTime::MIN))
+        .map_err(|_| {
+            format!(
+                "Invalid date: {}. Expected YYYYMMDD or YYYYMMDDTHHMMSS",
+                value
+            )
+        })
+}
+
+fn parse_list<T, F>(part: &str, value: &str, parse: F) -> Result<Vec<T>, String>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    value
+        .split(',')
+        .map(|item| {
+            parse(item.trim())
+                .ok_or_else(|| format!("Invalid RRULE part '{}': bad value '{}'", part, item))
+        })
+        .collect()
+}
+
+/// Parse a number in `min..=max`; with `signed`, `-max..=-min` is accepted too
+fn parse_number(item: &str, min: i64, max: i64, signed: bool) -> Option<i64> {
+    let number: i64 = item.trim_start_matches('+').parse().ok()?;
+    let magnitude = if signed { number.abs() } else { number };
+    (min..=max).contains(&magnitude).then_some(number)
+}
+
+impl RRule {
+    /// Parse "FREQ=..." or "DTSTART:...\nRRULE:FREQ=..." text. Without a
+    /// DTSTART the rule is anchored at `default_start` (local time)
+    pub fn parse(text: &str, default_start: NaiveDateTime) -> Result<RRule, String> {
+        let mut dtstart = None;
+        let mut rule = None;
+
+        for line in text.split_whitespace() {
+            let upper = line.to_uppercase();
+            if upper.starts_with("DTSTART") {
+                let (_, value) = upper
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid DTSTART line: {}", line))?;
+                dtstart = Some(parse_datetime_value(value)?);
+            } else if let Some(value) = upper.strip_prefix("RRULE:") {
+                rule = Some(value.to_string());
+            } else if upper.starts_with("FREQ=") {
+                rule = Some(upper);
+            } else {
+                return Err(format!("Unexpected line in RRULE: {}", line));
+            }
+        }
+
+        let rule = rule.ok_or("Missing RRULE (expected 'FREQ=...')")?;
+        let mut rrule = RRule {
+            dtstart: dtstart.unwrap_or(default_start),
+            freq: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_month: Vec::new(),
+            by_month_day: Vec::new(),
+            by_day: Vec::new(),
+            by_hour: Vec::new(),
+            by_minute: Vec::new(),
+            by_second: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+
+        let mut freq = None;
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid RRULE part '{}': expected KEY=VALUE", part))?;
+            match key {
+                "FREQ" => {
+                    freq = Some(match value {
+                        "YEARLY" => Frequency::Yearly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "WEEKLY" => Frequency::Weekly,
+                        "DAILY" => Frequency::Daily,
+                        "HOURLY" => Frequency::Hourly,
+                        "MINUTELY" => Frequency::Minutely,
+                        "SECONDLY" => {
+                            return Err("Invalid RRULE part 'FREQ=SECONDLY': not supported".into())
+                        }
+                        _ => {
+                            return Err(format!("Invalid RRULE part '{}': unknown frequency", part))
+                        }
+                    })
+                }
+                "INTERVAL" => {
+                    rrule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval >= 1)
+                        .ok_or_else(|| {
+                            format!("Invalid RRULE part '{}': expected a positive number", part)
+                        })?
+                }
+                "COUNT" => {
+                    rrule.count = Some(value.parse().ok().filter(|count| *count >= 1).ok_or_else(
+                        || format!("Invalid RRULE part '{}': expected a positive number", part),
+                    )?)
+                }
+                "UNTIL" => {
+                    rrule.until = Some(
+                        parse_datetime_value(value)
+                            .map_err(|err| format!("Invalid RRULE part '{}': {}", part, err))?,
+                    )
+                }
+                "BYMONTH" => {
+                    rrule.by_month = parse_list(part, value, |item| {
+                        parse_number(item, 1, 12, false).map(|n| n as u32)
+                    })?
+                }
+                "BYMONTHDAY" => {
+                    rrule.by_month_day = parse_list(part, value, |item| {
+                        parse_number(item, 1, 31, true).map(|n| n as i32)
+                    })?
+                }
+                "BYHOUR" => {
+                    rrule.by_hour = parse_list(part, value, |item| {
+                        parse_number(item, 0, 23, false).map(|n| n as u32)
+                    })?
+                }
+                "BYMINUTE" => {
+                    rrule.by_minute = parse_list(part, value, |item| {
+                        parse_number(item, 0, 59, false).map(|n| n as u32)
+                    })?
+                }
+                "BYSECOND" => {
+                    rrule.by_second = parse_list(part, value, |item| {
+                        parse_number(item, 0, 59, false).map(|n| n as u32)
+                    })?
+                }
+                "BYSETPOS" => {
+                    rrule.by_set_pos = parse_list(part, value, |item| {
+                        parse_number(item, 1, 366, true).map(|n| n as i32)
+                    })?
+                }
+                "BYDAY" => {
+                    rrule.by_day = parse_list(part, value, |item| {
+                        // The weekday is the last two characters, the rest an ordinal
+                        let (split, _) = item.char_indices().rev().nth(1)?;
+                        let weekday = parse_weekday(&item[split..])?;
+                        let ordinal = &item[..split];
+                        if ordinal.is_empty() {
+                            Some((None, weekday))
+                        } else {
+                            let ordinal = parse_number(ordinal, 1, 53, true)?;
+                            Some((Some(ordinal as i32), weekday))
+                        }
+                    })?
+                }
+                "WKST" => {
+                    rrule.week_start = parse_weekday(value)
+                        .ok_or_else(|| format!("Invalid RRULE part '{}': unknown weekday", part))?
+                }
+                "BYWEEKNO" | "BYYEARDAY" => {
+                    return Err(format!(
+                        "Invalid RRULE part '{}': {} is not supported",
+                        part, key
+                    ))
+                }
+                _ => return Err(format!("Invalid RRULE part '{}': unknown key", part)),
+            }
+        }
+
+        rrule.freq = freq.ok_or("Invalid RRULE: FREQ is required")?;
+        if rrule.count.is_some() && rrule.until.is_some() {
+            return Err("Invalid RRULE: COUNT and UNTIL cannot be combined".into());
+        }
+        if !matches!(rrule.freq, Frequency::Monthly | Frequency::Yearly)
+            && rrule.by_day.iter().any(|(ordinal, _)| ordinal.is_some())
+        {
+            return Err(
+                "Invalid RRULE part 'BYDAY': ordinals (e.g. 2TU) need FREQ=MONTHLY or FREQ=YEARLY"
+                    .into(),
+            );
+        }
+
+        Ok(rrule)
+    }
+
+    /// Occurrences strictly after `after`, in order
+    pub fn iter_after(&self, after: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let mut iter = RRuleIter::new(self);
+        // COUNT needs every occurrence since DTSTART, otherwise periods long
+        // gone can be skipped
+        if self.count.is_none() {
+            iter.step = self.steps_before(after.with_timezone(&Local).naive_local());
+        }
+        iter.skip_while(move |time| *time <= after)
+    }
+
+    /// First occurrence of the rule in local time, which may be later than DTSTART
+    pub fn first_occurrence(&self) -> Option<NaiveDateTime> {
+        RRuleIter::new(self)
+            .next()
+            .map(|time| time.with_timezone(&Local).naive_local())
+    }
+
+    /// The rule parts without DTSTART, as in an "RRULE:" property
+    pub fn rule_parts(&self) -> String {
+        let text = self.to_string();
+        text.split_once("RRULE:")
+            .map(|(_, rule)| rule.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Number of whole intervals between DTSTART and the period before `time`
+    fn steps_before(&self, time: NaiveDateTime) -> i64 {
+        let start = self.dtstart;
+        let periods = match self.freq {
+            Frequency::Yearly => (time.year() - start.year()) as i64,
+            Frequency::Monthly => {
+                (time.year() - start.year()) as i64 * 12 + time.month() as i64
+                    - start.month() as i64
+            }
+            Frequency::Weekly => (time - start).num_weeks(),
+            Frequency::Daily => (time - start).num_days(),
+            Frequency::Hourly => (time - start).num_hours(),
+            Frequency::Minutely => (time - start).num_minutes(),
+        };
+        (periods / self.interval as i64 - 1).max(0)
+    }
+
+    /// Candidate dates in the period `step` intervals after DTSTART
+    fn period_dates(&self, step: i64) -> Vec<NaiveDate> {
+        let start = self.dtstart.date();
+        match self.freq {
+            Frequency::Yearly => {
+                let year = start.year() + step as i32;
+                if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    // BYDAY ordinals count within the whole year
+                    let days = days_of_year(year);
+                    days.iter()
+                        .filter(|date| self.matches_by_day(**date, &days))
+                        .copied()
+                        .collect()
+                } else {
+                    let months: Vec<u32> = if !self.by_month.is_empty() {
+                        self.by_month.clone()
+                    } else if !self.by_month_day.is_empty() || !self.by_day.is_empty() {
+                        (1..=12).collect()
+                    } else {
+                        vec![start.month()]
+                    };
+                    months
+                        .into_iter()
+                        .flat_map(|month| self.month_dates(year, month))
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let index = start.year() as i64 * 12 + start.month0() as i64 + step;
+                let (year, month) = ((index / 12) as i32, (index % 12) as u32 + 1);
+                if !self.by_month.is_empty() && !self.by_month.contains(&month) {
+                    return Vec::new();
+                }
+                self.month_dates(year, month)
+            }
+            Frequency::Weekly => {
+                let offset = (start.weekday().num_days_from_monday() + 7
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week_start = start - Duration::days(offset as i64) + Duration::weeks(step);
+                (0..7)
+                    .map(|day| week_start + Duration::days(day))
+                    .filter(|date| {
+                        if self.by_day.is_empty() {
+                            date.weekday() == start.weekday()
+                        } else {
+                            self.by_day
+                                .iter()
+                                .any(|(_, weekday)| *weekday == date.weekday())
+                        }
+                    })
+                    .filter(|date| {
+                        self.by_month.is_empty() || self.by_month.contains(&date.month())
+                    })
+                    .collect()
+            }
+            Frequency::Daily => {
+                let date = start + Duration::days(step);
+                if self.limits_date(date) {
+                    vec![date]
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Hourly | Frequency::Minutely => Vec::new(),
+        }
+    }
+
+    /// Days of a month selected by BYMONTHDAY/BYDAY, or DTSTART's day of month
+    fn month_dates(&self, year: i32, month: u32) -> Vec<NaiveDate> {
+        let days = days_of_month(year, month);
+        if self.by_month_day.is_empty() && self.by_day.is_empty() {
+            return days
+                .into_iter()
+                .filter(|date| date.day() == self.dtstart.day())
+                .collect();
+        }
+        days.iter()
+            .filter(|date| {
+                self.by_month_day.is_empty() || self.matches_month_day(**date, days.len())
+            })
+            .filter(|date| self.by_day.is_empty() || self.matches_by_day(**date, &days))
+            .copied()
+            .collect()
+    }
+
+    fn matches_month_day(&self, date: NaiveDate, month_length: usize) -> bool {
+        let day = date.day() as i32;
+        let from_end = day - month_length as i32 - 1;
+        self.by_month_day.contains(&day) || self.by_month_day.contains(&from_end)
+    }
+
+    /// Match BYDAY, with ordinals counted within `span` (a month or a year)
+    fn matches_by_day(&self, date: NaiveDate, span: &[NaiveDate]) -> bool {
+        self.by_day.iter().any(|(ordinal, weekday)| {
+            if date.weekday() != *weekday {
+                return false;
+            }
+            match ordinal {
+                None => true,
+                Some(ordinal) => {
+                    let same_weekday: Vec<&NaiveDate> = span
+                        .iter()
+                        .filter(|day| day.weekday() == *weekday)
+                        .collect();
+                    let position = same_weekday
+                        .iter()
+                        .position(|day| **day == date)
+                        .unwrap_or(0) as i32;
+                    *ordinal == position + 1 || *ordinal == position - same_weekday.len() as i32
+                }
+            }
+        })
+    }
+
+    /// Whether a date passes the BYMONTH/BYMONTHDAY/BYDAY limits
+    fn limits_date(&self, date: NaiveDate) -> bool {
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (self.by_month_day.is_empty()
+                || self.matches_month_day(date, days_of_month(date.year(), date.month()).len()))
+            && (self.by_day.is_empty()
+                || self
+                    .by_day
+                    .iter()
+                    .any(|(_, weekday)| *weekday == date.weekday()))
+    }
+
+    /// All local candidate times in the period `step` intervals after DTSTART
+    fn period_candidates(&self, step: i64) -> Vec<NaiveDateTime> {
+        let or_default = |values: &Vec<u32>, default: u32| {
+            if values.is_empty() {
+                vec![default]
+            } else {
+                values.clone()
+            }
+        };
+        let seconds = or_default(&self.by_second, self.dtstart.second());
+
+        let mut candidates: Vec<NaiveDateTime> = match self.freq {
+            Frequency::Hourly | Frequency::Minutely => {
+                let (base, unit) = if self.freq == Frequency::Hourly {
+                    (
+                        self.dtstart.date().and_hms_opt(self.dtstart.hour(), 0, 0),
+                        Duration::hours(1),
+                    )
+                } else {
+                    (
+                        self.dtstart.date().and_hms_opt(
+                            self.dtstart.hour(),
+                            self.dtstart.minute(),
+                            0,
+                        ),
+                        Duration::minutes(1),
+                    )
+                };
+                let Some(period) = base.map(|base| base + unit * step as i32) else {
+                    return Vec::new();
+                };
+                if !self.limits_date(period.date())
+                    || !(self.by_hour.is_empty() || self.by_hour.contains(&period.hour()))
+                {
+                    return Vec::new();
+                }
+                let minutes = if self.freq == Frequency::Hourly {
+                    or_default(&self.by_minute, self.dtstart.minute())
+                } else if self.by_minute.is_empty() || self.by_minute.contains(&period.minute()) {
+                    vec![period.minute()]
+                } else {
+                    return Vec::new();
+                };
+                minutes
+                    .iter()
+                    .flat_map(|minute| {
+                        seconds.iter().filter_map(move |second| {
+                            period.with_minute(*minute)?.with_second(*second)
+                        })
+                    })
+                    .collect()
+            }
+            _ => {
+                let hours = or_default(&self.by_hour, self.dtstart.hour());
+                let minutes = or_default(&self.by_minute, self.dtstart.minute());
+                self.period_dates(step)
+                    .into_iter()
+                    .flat_map(|date| {
+                        let minutes = &minutes;
+                        let seconds = &seconds;
+                        hours.iter().flat_map(move |hour| {
+                            minutes.iter().flat_map(move |minute| {
+                                seconds.iter().filter_map(move |second| {
+                                    date.and_hms_opt(*hour, *minute, *second)
+                                })
+                            })
+                        })
+                    })
+                    .collect()
+            }
+        };
+
+        candidates.sort();
+        candidates.dedup();
+
+        if !self.by_set_pos.is_empty() {
+            let length = candidates.len() as i32;
+            let selected: Vec<NaiveDateTime> = self
+                .by_set_pos
+                .iter()
+                .filter_map(|pos| {
+                    let index = if *pos > 0 { pos - 1 } else { length + pos };
+                    (0..length)
+                        .contains(&index)
+                        .then(|| candidates[index as usize])
+                })
+                .collect();
+            candidates = selected;
+            candidates.sort();
+            candidates.dedup();
+        }
+
+        candidates
+    }
+}
+
+impl fmt::Display for RRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let freq = match self.freq {
+            Frequency::Yearly => "YEARLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Daily => "DAILY",
+            Frequency::Hourly => "HOURLY",
+            Frequency::Minutely => "MINUTELY",
+        };
+        let join = |values: Vec<String>| values.join(",");
+
+        let mut parts = vec![format!("FREQ={}", freq)];
+        if self.interval != 1 {
+            parts.push(format!("INTERVAL={}", self.interval));
+        }
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+        if let Some(until) = self.until {
+            parts.push(format!("UNTIL={}", until.format("%Y%m%dT%H%M%S")));
+        }
+        if !self.by_month.is_empty() {
+            parts.push(format!(
+                "BYMONTH={}",
+                join(self.by_month.iter().map(u32::to_string).collect())
+            ));
+        }
+        if !self.by_month_day.is_empty() {
+            parts.push(format!(
+                "BYMONTHDAY={}",
+                join(self.by_month_day.iter().map(i32::to_string).collect())
+            ));
+        }
+        if !self.by_day.is_empty() {
+            let days = self
+                .by_day
+                .iter()
+                .map(|(ordinal, weekday)| {
+                    format!(
+                        "{}{}",
+                        ordinal.map(|n| n.to_string()).unwrap_or_default(),
+                        weekday_code(*weekday)
+                    )
+                })
+                .collect();
+            parts.push(format!("BYDAY={}", join(days)));
+        }
+        if !self.by_hour.is_empty() {
+            parts.push(format!(
+                "BYHOUR={}",
+                join(self.by_hour.iter().map(u32::to_string).collect())
+            ));
+        }
+        if !self.by_minute.is_empty() {
+            parts.push(format!(
+                "BYMINUTE={}",
+                join(self.by_minute.iter().map(u32::to_string).collect())
+            ));
+        }
+        if !self.by_second.is_empty() {
+            parts.push(format!(
+                "BYSECOND={}",
+                join(self.by_second.iter().map(u32::to_string).collect())
+            ));
+        }
+        if !self.by_set_pos.is_empty() {
+            parts.push(format!(
+                "BYSETPOS={}",
+                join(self.by_set_pos.iter().map(i32::to_string).collect())
+            ));
+        }
+        if self.week_start != Weekday::Mon {
+            parts.push(format!("WKST={}", weekday_code(self.week_start)));
+        }
+
+        write!(
+            f,
+            "DTSTART:{}\nRRULE:{}",
+            self.dtstart.format("%Y%m%dT%H%M%S"),
+            parts.join(";")
+        )
+    }
+}
+
+fn days_of_month(year: i32, month: u32) -> Vec<NaiveDate> {
+    (1..=31)
+        .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .collect()
+}
+
+fn days_of_year(year: i32) -> Vec<NaiveDate> {
+    (1..=366)
+        .filter_map(|ordinal| NaiveDate::from_yo_opt(year, ordinal))
+        .collect()
+}
+
+/// Iterates over all occurrences of a rule, starting at DTSTART
+struct RRuleIter<'a> {
+    rule: &'a RRule,
+    step: i64,
+    empty_periods: u32,
+    emitted: u32,
+    buffer: VecDeque<NaiveDateTime>,
+    done: bool,
+}
+
+impl<'a> RRuleIter<'a> {
+    fn new(rule: &'a RRule) -> Self {
+        RRuleIter {
+            rule,
+            step: 0,
+            empty_periods: 0,
+            emitted: 0,
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl Iterator for RRuleIter<'_> {
+    type Item = DateTime<Utc>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if let Some(local) = self.buffer.pop_front() {
+                if self.rule.until.is_some_and(|until| local > until)
+                    || self.rule.count.is_some_and(|count| self.emitted >= count)
+                {
+                    self.done = true;
+                    return None;
+                }
+                self.emitted += 1;
+                // Times skipped by a DST change do not exist and are dropped
+                match Local.from_local_datetime(&local).earliest() {
+                    Some(time) => return Some(time.with_timezone(&Utc)),
+                    None => continue,
+                }
+            }
+
+            if self.empty_periods >= MAX_EMPTY_PERIODS {
+                self.done = true;
+                return None;
+            }
+            let candidates = self
+                .rule
+                .period_candidates(self.step * self.rule.interval as i64);
+            self.step += 1;
+            let dtstart = self.rule.dtstart;
+            self.buffer
+                .extend(candidates.into_iter().filter(|time| *time >= dtstart));
+            if self.buffer.is_empty() {
+                self.empty_periods += 1;
+            } else {
+                self.empty_periods = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    /// The first `n` occurrences of a rule, in local time
+    fn occurrences(text: &str, n: usize) -> Vec<String> {
+        let rule = RRule::parse(text, local("2024-01-01 09:00")).unwrap();
+        let before_start = Local
+            .from_local_datetime(&(rule.dtstart - Duration::seconds(1)))
+            .unwrap()
+            .with_timezone(&Utc);
+        rule.iter_after(before_start)
+            .take(n)
+            .map(|time| {
+                time.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn by_day_weekly() {
+        assert_eq!(
+            occurrences("FREQ=WEEKLY;BYDAY=TU,TH", 4),
+            [
+                "2024-01-02 09:00",
+                "2024-01-04 09:00",
+                "2024-01-09 09:00",
+                "2024-01-11 09:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn by_day_ordinals() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY;BYDAY=2TU,-1FR", 4),
+            [
+                "2024-01-09 09:00",
+                "2024-01-26 09:00",
+                "2024-02-13 09:00",
+                "2024-02-23 09:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn by_day_rejects_bad_values() {
+        let start = local("2024-01-01 09:00");
+        for rule in [
+            "FREQ=WEEKLY;BYDAY=€",
+            "FREQ=WEEKLY;BYDAY=X",
+            "FREQ=WEEKLY;BYDAY=1€U",
+        ] {
+            assert!(RRule::parse(rule, start).is_err(), "{}", rule);
+        }
+        assert!(RRule::parse("FREQ=WEEKLY;BYDAY=2TU", start).is_err());
+    }
+
+    #[test]
+    fn by_month_day() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY;BYMONTHDAY=15,-1", 5),
+            [
+                "2024-01-15 09:00",
+                "2024-01-31 09:00",
+                "2024-02-15 09:00",
+                "2024-02-29 09:00",
+                "2024-03-15 09:00"
+            ]
+        );
+        // Months without a 31st are skipped
+        assert_eq!(
+            occurrences("FREQ=MONTHLY;BYMONTHDAY=31", 3),
+            ["2024-01-31 09:00", "2024-03-31 09:00", "2024-05-31 09:00"]
+        );
+    }
+
+    #[test]
+    fn count_limits_occurrences() {
+        assert_eq!(
+            occurrences(
+                "DTSTART:20240105T180000\nRRULE:FREQ=DAILY;INTERVAL=2;COUNT=3",
+                10
+            ),
+            ["2024-01-05 18:00", "2024-01-07 18:00", "2024-01-09 18:00"]
+        );
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        assert_eq!(
+            occurrences(
+                "DTSTART:20240101T080000\nRRULE:FREQ=WEEKLY;UNTIL=20240115T080000",
+                10
+            ),
+            ["2024-01-01 08:00", "2024-01-08 08:00", "2024-01-15 08:00"]
+        );
+    }
+
+    #[test]
+    fn count_and_until_cannot_be_combined() {
+        assert!(RRule::parse(
+            "FREQ=DAILY;COUNT=2;UNTIL=20240110",
+            local("2024-01-01 09:00")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let start = local("2024-01-01 09:00");
+        let rule = RRule::parse("FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR;BYHOUR=17", start).unwrap();
+        assert_eq!(RRule::parse(&rule.to_string(), start).unwrap(), rule);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::cron_expr::{scheduler_cron, YearField};
+use crate::database::Poke;
+use crate::rrule::RRule;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use croner::Cron;
+
+/// Upper bound on occurrences inspected when looking for upcoming runs, so
+/// that a job excluded on every date cannot loop forever
+const MAX_SCANNED_OCCURRENCES: usize = 10_000;
+
+/// Common schedules offered by interactive interfaces, as (description,
+/// expression). Times of day are RRULEs so that they are in local time
+pub const SCHEDULE_PRESETS: &[(&str, &str)] = &[
+    (
+        "Every weekday at 09:00",
+        "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=9",
+    ),
+    ("Every day at 09:00", "FREQ=DAILY;BYHOUR=9"),
+    ("Every hour", "FREQ=HOURLY"),
+    ("Every 30 minutes", "every 30m"),
+    ("Every Monday at 10:00", "FREQ=WEEKLY;BYDAY=MO;BYHOUR=10"),
+    (
+        "First day of the month at 09:00",
+        "FREQ=MONTHLY;BYMONTHDAY=1;BYHOUR=9",
+    ),
+    ("Only as a follow-up", "manual"),
+];
+
+/// Local dates on which a job must not fire, from its exclusion calendars
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    ranges: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl Exclusions {
+    pub fn new(ranges: Vec<(NaiveDate, NaiveDate)>) -> Self {
+        Exclusions { ranges }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Whether the local date of `time` falls in any excluded range
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let date = time.with_timezone(&Local).date_naive();
+        self.ranges
+            .iter()
+            .any(|(start, end)| *start <= date && date <= *end)
+    }
+}
+
+/// Number of occurrences inspected to check that a jitter window fits between runs
+const JITTER_CHECKED_OCCURRENCES: usize = 100;
+
+/// Random delay applied to each occurrence of a job. The delay is derived from
+/// the seed and the occurrence time, so every process computes the same one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jitter {
+    pub window_secs: i64,
+    pub seed: i64,
+}
+
+impl Jitter {
+    /// Delay of the occurrence at `time`, in `[0, window)`
+    pub fn offset(&self, time: DateTime<Utc>) -> chrono::Duration {
+        // splitmix64 finaliser over the seed and the occurrence timestamp
+        let mut x =
+            (self.seed as u64) ^ (time.timestamp() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        chrono::Duration::seconds((x % self.window_secs.max(1) as u64) as i64)
+    }
+}
+
+/// A parsed job schedule, either a cron expression or an RRULE
+pub enum Schedule {
+    /// Cron expression, optionally restricted to certain years
+    Cron(Cron, Option<YearField>),
+    RRule(RRule),
+    /// Fixed interval counted from an anchor time
+    Interval {
+        anchor: DateTime<Utc>,
+        every: chrono::Duration,
+    },
+    /// No schedule of its own, the job only fires as a follow-up
+    Manual,
+    /// Another schedule with each occurrence delayed by a random amount within a window
+    Jittered(Box<Schedule>, Jitter),
+}
+
+impl Schedule {
+    /// Parse the schedule of a job
+    pub fn for_poke(poke: &Poke) -> Result<Schedule, String> {
+        let schedule = Schedule::base_for_poke(poke)?;
+        Ok(match poke.jitter() {
+            Some(jitter) => Schedule::Jittered(Box::new(schedule), jitter),
+            None => schedule,
+        })
+    }
+
+    /// Parse the schedule of a job, ignoring its jitter
+    fn base_for_poke(poke: &Poke) -> Result<Schedule, String> {
+        if poke.is_manual() {
+            return Ok(Schedule::Manual);
+        }
+        if let Some(interval_secs) = poke.interval_secs {
+            return Ok(Schedule::Interval {
+                anchor: to_utc(poke.anchor_at.unwrap_or(poke.created)),
+                every: chrono::Duration::seconds(interval_secs.max(1)),
+            });
+        }
+        match &poke.rrule {
+            Some(rrule) => {
+                let default_start = to_utc(poke.created).with_timezone(&Local).naive_local();
+                Ok(Schedule::RRule(RRule::parse(rrule, default_start)?))
+            }
+            None => Ok(Schedule::Cron(
+                parse_cron(&poke.cron)?,
+                YearField::from_cron(&poke.cron),
+            )),
+        }
+    }
+
+    /// Occurrences strictly after `after`, in order
+    pub fn iter_after(&self, after: DateTime<Utc>) -> Box<dyn Iterator<Item = DateTime<Utc>> + '_> {
+        match self {
+            Schedule::Cron(cron, None) => Box::new(cron.iter_after(after)),
+            Schedule::Cron(cron, Some(years)) => Box::new(
+                cron.iter_after(after)
+                    .take_while(|time| time.year() <= years.last_year())
+                    .filter(|time| years.matches(time.year())),
+            ),
+            Schedule::RRule(rrule) => Box::new(rrule.iter_after(after)),
+            Schedule::Interval { anchor, every } => {
+                // Index of the first occurrence strictly after `after`
+                let elapsed = (after - *anchor).num_seconds();
+                let first = if elapsed < 0 {
+                    1
+                } else {
+                    elapsed / every.num_seconds() + 1
+                };
+                let (anchor, every_secs) = (*anchor, every.num_seconds());
+                Box::new(
+                    (first..)
+                        .map(move |index| anchor + chrono::Duration::seconds(every_secs * index)),
+                )
+            }
+            Schedule::Manual => Box::new(std::iter::empty()),
+            Schedule::Jittered(schedule, jitter) => Box::new(
+                schedule
+                    .iter_after(after - chrono::Duration::seconds(jitter.window_secs))
+                    .map(|time| time + jitter.offset(time))
+                    .filter(move |time| *time > after),
+            ),
+        }
+    }
+
+    /// Compute all occurrences in the half-open interval `(start, end]`, capped at `limit`
+    pub fn occurrences_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        self.iter_after(start)
+            .take_while(|time| *time <= end)
+            .take(limit)
+            .collect()
+    }
+}
+
+/// Check that a job's jitter window is shorter than the time between its runs,
+/// so that delayed occurrences stay in order
+pub fn validate_jitter(poke: &Poke) -> Result<(), String> {
+    let Some(jitter) = poke.jitter() else {
+        return Ok(());
+    };
+    if jitter.window_secs < 1 {
+        return Err(format!(
+            "Jitter must be at least 1 second, got {}",
+            jitter.window_secs
+        ));
+    }
+
+    let runs: Vec<_> = Schedule::base_for_poke(poke)?
+        .iter_after(Utc::now())
+        .take(JITTER_CHECKED_OCCURRENCES)
+        .collect();
+    let shortest_gap = runs
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_seconds())
+        .min();
+    if let Some(gap) = shortest_gap.filter(|gap| jitter.window_secs >= *gap) {
+        return Err(format!(
+            "Jitter of {} must be shorter than the {} between runs",
+            format_duration(jitter.window_secs),
+            format_duration(gap)
+        ));
+    }
+    Ok(())
+}
+
+/// Parse a duration such as "45m", "2h", "1h30m", "90s" or "1d" into seconds
+pub fn parse_duration(value: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration: {}. Expected e.g. '90s', '45m', '2h' or '1h30m'",
+            value
+        )
+    };
+    let mut total: i64 = 0;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return Err(invalid()),
+        };
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    // A bare number means seconds
+    if !number.is_empty() {
+        total += number.parse::<i64>().map_err(|_| invalid())?;
+    } else if total == 0 && value.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Format a number of seconds compactly, e.g. "1h30m"
+pub fn format_duration(seconds: i64) -> String {
+    if seconds == 0 {
+        return "0s".to_string();
+    }
+    let mut result = String::new();
+    let mut rest = seconds;
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if rest >= size {
+            result.push_str(&format!("{}{}", rest / size, unit));
+            rest %= size;
+        }
+    }
+    result
+}
+
+/// Parse a cron expression the same way tokio-cron-scheduler does, so that
+/// computed occurrences line up with what the scheduler actually fires. A
+/// trailing year field is ignored here and applied by `Schedule`
+pub fn parse_cron(cron: &str) -> Result<Cron, String> {
+    Cron::new(&scheduler_cron(cron))
+        .with_seconds_required()
+        .with_dom_and_dow()
+        .parse()
+        .map_err(|err| format!("Invalid cron expression '{}': {}", cron, err))
+}
+
+/// Compute up to `count` upcoming runs of a job after `after`, honouring its
+/// active period and skipping excluded dates
+pub fn upcoming_runs(
+    poke: &Poke,
+    exclusions: &Exclusions,
+    after: DateTime<Utc>,
+    count: usize,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    let schedule = Schedule::for_poke(poke)?;
+    let start = match poke.starts_at {
+        // Step back one second so a run exactly at the start time is included
+        Some(starts_at) => after.max(to_utc(starts_at) - chrono::Duration::seconds(1)),
+        None => after,
+    };
+
+    Ok(schedule
+        .iter_after(start)
+        .take(MAX_SCANNED_OCCURRENCES)
+        .take_while(|time| poke.ends_at.is_none_or(|ends_at| *time <= to_utc(ends_at)))
+        .filter(|time| !exclusions.contains(*time))
+        .take(count)
+        .collect())
+}
+
+/// Interpret a stored naive timestamp as UTC
+pub fn to_utc(naive: NaiveDateTime) -> DateTime<Utc> {
+    DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc)
+}
+
+/// Parse a local date ("2024-05-01") or date and time ("2024-05-01 18:30") into
+/// a UTC timestamp. A bare date means the start of the day, or its last second
+/// when `end_of_day` is set
+pub fn parse_local_datetime(value: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    let value = value.trim();
+    let naive = if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time = if end_of_day {
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default()
+        } else {
+            NaiveTime::MIN
+        };
+        date.and_time(time)
+    } else {
+        [
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+        ]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| {
+            format!(
+                "Invalid date: {}. Expected 'YYYY-MM-DD' or 'YYYY-MM-DD HH:MM'",
+                value
+            )
+        })?
+    };
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.naive_utc())
+        .ok_or_else(|| format!("Date {} does not exist in the local timezone", value))
+}
+
+/// Format a stored UTC timestamp in local time for display
+pub fn format_local(utc: NaiveDateTime) -> String {
+    to_utc(utc)
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Parse a plain "YYYY-MM-DD" date
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {}. Expected 'YYYY-MM-DD'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jittered(schedule: &str, window_secs: i64) -> Poke {
+        let mut poke = Poke::new("jittered", schedule, None, false).unwrap();
+        poke.jitter_secs = Some(window_secs);
+        poke.jitter_seed = 42;
+        poke
+    }
+
+    #[test]
+    fn same_seed_and_occurrence_give_same_offset() {
+        let jitter = Jitter {
+            window_secs: 600,
+            seed: 42,
+        };
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let copy = Jitter {
+            window_secs: 600,
+            seed: 42,
+        };
+        assert_eq!(jitter.offset(time), copy.offset(time));
+    }
+
+    #[test]
+    fn offsets_stay_within_window() {
+        let jitter = Jitter {
+            window_secs: 90,
+            seed: 7,
+        };
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        for minute in 0..1000 {
+            let offset = jitter.offset(start + chrono::Duration::minutes(minute));
+            assert!(offset >= chrono::Duration::zero());
+            assert!(offset < chrono::Duration::seconds(90));
+        }
+    }
+
+    #[test]
+    fn jittered_occurrences_are_delayed_within_window() {
+        let poke = jittered("every 30m", 600);
+        let base = Schedule::base_for_poke(&poke).unwrap();
+        let schedule = Schedule::for_poke(&poke).unwrap();
+        let after = Utc::now();
+        let runs: Vec<_> = base.iter_after(after).take(5).collect();
+        for run in &runs {
+            let delayed = *run + poke.jitter().unwrap().offset(*run);
+            assert!(*run <= delayed && delayed < *run + chrono::Duration::seconds(600));
+            assert!(schedule
+                .iter_after(after)
+                .take(6)
+                .any(|time| time == delayed));
+        }
+    }
+
+    #[test]
+    fn window_wider_than_period_is_rejected() {
+        assert!(validate_jitter(&jittered("every 30m", 600)).is_ok());
+        assert!(validate_jitter(&jittered("every 30m", 1800)).is_err());
+        assert!(validate_jitter(&jittered("FREQ=HOURLY", 7200)).is_err());
+        assert!(validate_jitter(&jittered("every 30m", 0)).is_err());
+    }
+}
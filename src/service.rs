@@ -1,28 +1,52 @@
 use crate::{
-    database::{establish_connection, list_pokes},
-    notification::setup_notification,
+    api, config,
+    database::backup::daily_backup,
+    database::calendar::get_poke_exclusions,
+    database::followup::take_due_followups,
+    database::trash::purge_trash,
+    database::{
+        anchor_start_intervals, establish_connection, get_change_version, get_poke_stats,
+        list_pokes, set_poke_status, MissedPolicy, Poke, PokeStatus,
+    },
+    dbus, focus,
+    jobfile::FileWatch,
+    notification::{fire_poke, setup_notification, JobHandle, LATE_TICK_GRACE_SECS},
+    schedule::{to_utc, Exclusions, Schedule},
+    timer,
 };
-use log::{error, info};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use log::{error, info, warn};
 use notify_rust::Notification;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Notify;
 use tokio_cron_scheduler::JobScheduler;
 
-const PID_FILE: &str = "/tmp/poke_me.pid";
-const CONTROL_FILE: &str = "/tmp/poke_me.control";
-const REFRESH_FILE: &str = "/tmp/poke_me.refresh";
+/// Wall-clock drift beyond monotonic time that is treated as a suspend/resume or clock change
+const CLOCK_JUMP_THRESHOLD_SECS: i64 = 30;
 
-/// Run the background notification service
-pub async fn run_service(daemon: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Upper bound on notifications fired for a single job by the "each" policy
+const MAX_CATCH_UP_FIRES: usize = 10;
+
+/// Run the background notification service, with the HTTP API on `api_port`
+/// if given and on the session bus if `dbus` is set. A job file given in
+/// `watch` is applied at start and whenever it changes
+pub async fn run_service(
+    daemon: bool,
+    api_port: Option<u16>,
+    dbus: bool,
+    mut watch: Option<FileWatch>,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting Poke Me notification service...");
 
     // Create PID file
     let pid = std::process::id();
-    fs::write(PID_FILE, pid.to_string())?;
+    fs::write(&config::get().pid_file, pid.to_string())?;
     info!("Service PID: {}", pid);
 
     // Create control file for graceful shutdown
-    fs::write(CONTROL_FILE, "running")?;
+    fs::write(&config::get().control_file, "running")?;
 
     // Establish database connection
     let pool = establish_connection().await?;
@@ -30,22 +54,39 @@ pub async fn run_service(daemon: bool) -> Result<(), Box<dyn std::error::Error>>
     // Create and start the job scheduler
     let mut sched = JobScheduler::new().await?;
 
+    // Intervals anchored on service start count from now
+    anchor_start_intervals(&pool, Utc::now().naive_utc()).await?;
+
     // Load existing jobs from database and set them up
-    load_jobs_into_scheduler(&pool, &mut sched).await?;
+    let mut loaded_jobs = LoadedJobs::new();
+    let mut change_version = get_change_version(&pool).await?;
+    load_jobs_into_scheduler(&pool, &mut sched, &mut loaded_jobs).await?;
+
+    // Deal with occurrences missed while the service was not running
+    catch_up_missed(&pool).await;
 
     // Start the scheduler
     sched.start().await?;
     info!("Scheduler started successfully");
 
+    // Changes made through the API wake the loop below instead of waiting for the next tick
+    let changed = Arc::new(Notify::new());
+    if let Some(port) = api_port {
+        tokio::spawn(api::serve(pool.clone(), port, changed.clone()));
+    }
+    if dbus {
+        dbus::serve(pool.clone(), changed.clone()).await;
+    }
+
     // Show initial notification
     let _ = Notification::new()
-        .appname("Poke Me")
+        .appname(&config::get().app_name)
         .summary("Service Started")
         .body(&format!(
             "Notification service running with {} jobs",
             list_pokes(&pool, None).await?.len()
         ))
-        .icon("clock")
+        .icon(&config::get().default_icon)
         .show();
 
     if daemon {
@@ -55,29 +96,121 @@ pub async fn run_service(daemon: bool) -> Result<(), Box<dyn std::error::Error>>
         info!("Service running. Press Ctrl+C to stop or use 'poke_me stop' from another terminal.");
     }
 
+    // Day of the last daily backup and trash purge, checked on every tick so
+    // that a service running for days keeps doing them
+    let mut backup_day: Option<NaiveDate> = None;
+
+    // Track monotonic and wall-clock time to notice suspends and clock changes
+    let mut last_instant = std::time::Instant::now();
+    let mut last_wall = Utc::now();
+
     // Keep the service running
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => {}
+            _ = changed.notified() => {}
+        }
+
+        // The monotonic clock stops while suspended, the wall clock does not
+        let now_instant = std::time::Instant::now();
+        let now_wall = Utc::now();
+        let monotonic_elapsed = Duration::from_std(now_instant - last_instant).unwrap_or_default();
+        let wall_elapsed = now_wall - last_wall;
+        last_instant = now_instant;
+        last_wall = now_wall;
+        if (wall_elapsed - monotonic_elapsed).num_seconds().abs() > CLOCK_JUMP_THRESHOLD_SECS {
+            info!(
+                "Wall clock jumped by {}s (resume from suspend?), checking for missed jobs...",
+                (wall_elapsed - monotonic_elapsed).num_seconds()
+            );
+            catch_up_missed(&pool).await;
+        }
 
         // Check if we should stop
-        if !Path::new(CONTROL_FILE).exists() {
+        if !config::get().control_file.exists() {
             info!("Control file removed, shutting down gracefully...");
             break;
         }
 
         // Check if we should refresh jobs
-        if Path::new(REFRESH_FILE).exists() {
+        if config::get().refresh_file.exists() {
             info!("Refresh file detected, reloading jobs...");
-            fs::remove_file(REFRESH_FILE)?;
+            fs::remove_file(&config::get().refresh_file)?;
 
             // Clear existing jobs and reload
+            for (_, (_, handle)) in loaded_jobs.drain() {
+                handle.remove(&sched).await?;
+            }
             sched.shutdown().await?;
             sched = JobScheduler::new().await?;
-            load_jobs_into_scheduler(&pool, &mut sched).await?;
+            change_version = get_change_version(&pool).await?;
+            load_jobs_into_scheduler(&pool, &mut sched, &mut loaded_jobs).await?;
             sched.start().await?;
             info!("Jobs refreshed successfully");
         }
 
+        let today = Local::now().date_naive();
+        if backup_day != Some(today) {
+            backup_day = Some(today);
+            match daily_backup(&pool).await {
+                Ok(Some(path)) => info!("Daily backup written to {}", path.display()),
+                Ok(None) => {}
+                Err(err) => error!("Daily backup failed: {}", err),
+            }
+            let trash_days = config::get().trash_days;
+            if trash_days > 0 {
+                let before = Utc::now().naive_utc() - Duration::days(trash_days.into());
+                match purge_trash(&pool, Some(before)).await {
+                    Ok(0) => {}
+                    Ok(purged) => info!(
+                        "Purged {} job(s) trashed more than {} days ago",
+                        purged, trash_days
+                    ),
+                    Err(err) => error!("Failed to purge the trash: {}", err),
+                }
+            }
+        }
+
+        // Apply the job file if it was edited
+        if let Some(watch) = &mut watch {
+            watch.check(&pool, &sched).await;
+        }
+
+        // Check if the database changed since the last sync
+        match get_change_version(&pool).await {
+            Ok(version) if version != change_version => {
+                info!("Database change detected, syncing jobs...");
+                change_version = version;
+                if let Err(err) = sync_jobs(&pool, &sched, &mut loaded_jobs).await {
+                    error!("Failed to sync jobs: {}", err);
+                }
+                dbus::jobs_changed().await;
+            }
+            Ok(_) => {}
+            Err(err) => error!("Failed to read change version: {}", err),
+        }
+
+        // Fire follow-ups whose delay has passed
+        match take_due_followups(&pool).await {
+            Ok(pokes) => {
+                for poke in pokes
+                    .into_iter()
+                    .filter(|poke| poke.status == PokeStatus::Active)
+                {
+                    info!("Firing follow-up '{}'", poke.name);
+                    let pool = pool.clone();
+                    tokio::spawn(async move { fire_poke(&pool, &poke, Utc::now(), 0).await });
+                }
+            }
+            Err(err) => error!("Failed to read queued follow-ups: {}", err),
+        }
+
+        // Move the focus session to its next phase when due
+        focus::tick(&pool).await;
+
+        // Fire countdown timers that ran out
+        timer::tick(&pool).await;
+
         // Optional: periodic health check
         if let Err(err) = pool.acquire().await {
             error!("Database connection error: {}", err);
@@ -89,18 +222,77 @@ pub async fn run_service(daemon: bool) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+/// Jobs currently scheduled, keyed by poke id
+type LoadedJobs = HashMap<i64, (Poke, JobHandle)>;
+
 /// Load jobs from database into the scheduler
 async fn load_jobs_into_scheduler(
     pool: &sqlx::SqlitePool,
     sched: &mut JobScheduler,
+    loaded_jobs: &mut LoadedJobs,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Load existing jobs from database and set them up
-    let existing_jobs = list_pokes(pool, None).await?;
+    let existing_jobs = list_active_pokes(pool).await?;
     info!("Found {} existing scheduled jobs", existing_jobs.len());
 
-    for poke in &existing_jobs {
-        match setup_notification(&poke, sched).await {
-            Ok(()) => info!("Loaded job: {}", poke.name),
+    for poke in existing_jobs {
+        match setup_notification(pool, &poke, sched).await {
+            Ok(handle) => {
+                info!("Loaded job: {}", poke.name);
+                loaded_jobs.insert(poke.id, (poke, handle));
+            }
+            Err(err) => error!("Failed to load job {}: {}", poke.name, err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Jobs that should currently be scheduled
+async fn list_active_pokes(pool: &sqlx::SqlitePool) -> Result<Vec<Poke>, sqlx::Error> {
+    Ok(list_pokes(pool, None)
+        .await?
+        .into_iter()
+        .filter(|poke| poke.status == PokeStatus::Active)
+        .collect())
+}
+
+/// Bring the scheduler in line with the database, only touching jobs that
+/// were added, removed or modified since the last sync
+async fn sync_jobs(
+    pool: &sqlx::SqlitePool,
+    sched: &JobScheduler,
+    loaded_jobs: &mut LoadedJobs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let current: HashMap<i64, Poke> = list_active_pokes(pool)
+        .await?
+        .into_iter()
+        .map(|poke| (poke.id, poke))
+        .collect();
+
+    // Drop jobs that were removed or changed
+    let stale: Vec<i64> = loaded_jobs
+        .iter()
+        .filter(|(id, (poke, _))| current.get(id) != Some(poke))
+        .map(|(id, _)| *id)
+        .collect();
+    for id in stale {
+        if let Some((poke, handle)) = loaded_jobs.remove(&id) {
+            handle.remove(sched).await?;
+            info!("Unloaded job: {}", poke.name);
+        }
+    }
+
+    // Add jobs that are new or were just unloaded because they changed
+    for (id, poke) in current {
+        if loaded_jobs.contains_key(&id) {
+            continue;
+        }
+        match setup_notification(pool, &poke, sched).await {
+            Ok(handle) => {
+                info!("Loaded job: {}", poke.name);
+                loaded_jobs.insert(id, (poke, handle));
+            }
             Err(err) => error!("Failed to load job {}: {}", poke.name, err),
         }
     }
@@ -108,27 +300,148 @@ async fn load_jobs_into_scheduler(
     Ok(())
 }
 
+/// Apply each job's missed policy to occurrences that passed without firing
+async fn catch_up_missed(pool: &sqlx::SqlitePool) {
+    let pokes = match list_active_pokes(pool).await {
+        Ok(pokes) => pokes,
+        Err(err) => {
+            error!("Failed to load jobs for catch-up: {}", err);
+            return;
+        }
+    };
+
+    // Occurrences within the grace period are still fired by the scheduler itself
+    let cutoff = Utc::now() - Duration::seconds(LATE_TICK_GRACE_SECS);
+
+    for poke in pokes {
+        let stats = match get_poke_stats(pool, poke.id).await {
+            Ok(stats) => stats,
+            Err(err) => {
+                error!("Failed to read history for '{}': {}", poke.name, err);
+                continue;
+            }
+        };
+
+        if poke.is_exhausted(stats.run_count, Utc::now().naive_utc()) {
+            info!("Job '{}' is exhausted, marking it completed", poke.name);
+            if let Err(err) = set_poke_status(pool, poke.id, PokeStatus::Completed).await {
+                error!("Failed to complete job '{}': {}", poke.name, err);
+            }
+            continue;
+        }
+
+        // Only occurrences inside the job's active period count as missed
+        let mut since = stats.last_fired.unwrap_or(poke.created);
+        if let Some(starts_at) = poke.starts_at {
+            since = since.max(starts_at);
+        }
+        let mut until = cutoff.naive_utc();
+        if let Some(ends_at) = poke.ends_at {
+            until = until.min(ends_at);
+        }
+        if since >= until {
+            continue;
+        }
+
+        let exclusions = match get_poke_exclusions(pool, poke.id).await {
+            Ok(exclusions) => exclusions,
+            Err(err) => {
+                error!("Failed to read calendars for '{}': {}", poke.name, err);
+                continue;
+            }
+        };
+
+        let mut missed = match missed_runs(&poke, &exclusions, to_utc(since), to_utc(until)) {
+            Ok(missed) => missed,
+            Err(err) => {
+                error!("Failed to compute missed runs for '{}': {}", poke.name, err);
+                continue;
+            }
+        };
+        // Never fire more often than the job has runs left
+        if let Some(max_runs) = poke.max_runs {
+            missed.truncate((max_runs - stats.run_count).max(0) as usize);
+        }
+        let Some(&last_missed) = missed.last() else {
+            continue;
+        };
+
+        match poke.missed_policy {
+            MissedPolicy::Skip => {
+                info!(
+                    "Job '{}' missed {} time(s), skipping",
+                    poke.name,
+                    missed.len()
+                );
+            }
+            MissedPolicy::Once => {
+                info!(
+                    "Job '{}' missed {} time(s), firing once",
+                    poke.name,
+                    missed.len()
+                );
+                fire_poke(pool, &poke, last_missed, missed.len() as i64).await;
+            }
+            MissedPolicy::Each => {
+                if missed.len() > MAX_CATCH_UP_FIRES {
+                    warn!(
+                        "Job '{}' missed {} times, only firing the last {}",
+                        poke.name,
+                        missed.len(),
+                        MAX_CATCH_UP_FIRES
+                    );
+                }
+                info!(
+                    "Job '{}' missed {} time(s), firing each",
+                    poke.name,
+                    missed.len()
+                );
+                let skipped = missed.len().saturating_sub(MAX_CATCH_UP_FIRES);
+                for occurrence in &missed[skipped..] {
+                    fire_poke(pool, &poke, *occurrence, 1).await;
+                }
+            }
+        }
+    }
+}
+
+/// The occurrences of a job between its last firing and the cutoff, leaving
+/// out dates excluded by its calendars
+fn missed_runs(
+    poke: &Poke,
+    exclusions: &Exclusions,
+    since: DateTime<Utc>,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    // Very frequent jobs left alone for a long time would otherwise iterate for ages
+    const COUNT_LIMIT: usize = 10_000;
+    Ok(Schedule::for_poke(poke)?
+        .occurrences_between(since, cutoff, COUNT_LIMIT)
+        .into_iter()
+        .filter(|time| !exclusions.contains(*time))
+        .collect())
+}
+
 /// Signal the service to refresh its jobs
 pub fn signal_refresh() -> Result<(), Box<dyn std::error::Error>> {
-    if !Path::new(PID_FILE).exists() {
+    if !config::get().pid_file.exists() {
         return Err("Service is not running".into());
     }
 
     // Create refresh file to signal the service
-    fs::write(REFRESH_FILE, "refresh")?;
-    println!("Refresh signal sent to service");
+    fs::write(&config::get().refresh_file, "refresh")?;
     Ok(())
 }
 
 /// Stop the running service
 pub fn stop_service() -> Result<(), Box<dyn std::error::Error>> {
     // Check if service is running
-    if !Path::new(PID_FILE).exists() {
+    if !config::get().pid_file.exists() {
         return Err("Service is not running".into());
     }
 
     // Read PID from file
-    let pid_content = fs::read_to_string(PID_FILE)?;
+    let pid_content = fs::read_to_string(&config::get().pid_file)?;
     let pid: u32 = pid_content.trim().parse()?;
 
     // Check if process is still running
@@ -138,8 +451,8 @@ pub fn stop_service() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Remove control file to signal graceful shutdown
-    if Path::new(CONTROL_FILE).exists() {
-        fs::remove_file(CONTROL_FILE)?;
+    if config::get().control_file.exists() {
+        fs::remove_file(&config::get().control_file)?;
         println!("Stopping service (PID: {})...", pid);
 
         // Wait a bit for graceful shutdown
@@ -159,6 +472,14 @@ pub fn stop_service() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Whether the background service is currently running
+pub fn is_service_running() -> bool {
+    fs::read_to_string(&config::get().pid_file)
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+        .is_some_and(is_process_running)
+}
+
 /// Check if a process is running
 fn is_process_running(pid: u32) -> bool {
     unsafe { libc::kill(pid as i32, 0) == 0 }
@@ -166,14 +487,14 @@ fn is_process_running(pid: u32) -> bool {
 
 /// Clean up service files
 fn cleanup_service_files() -> Result<(), Box<dyn std::error::Error>> {
-    if Path::new(PID_FILE).exists() {
-        fs::remove_file(PID_FILE)?;
+    if config::get().pid_file.exists() {
+        fs::remove_file(&config::get().pid_file)?;
     }
-    if Path::new(CONTROL_FILE).exists() {
-        fs::remove_file(CONTROL_FILE)?;
+    if config::get().control_file.exists() {
+        fs::remove_file(&config::get().control_file)?;
     }
-    if Path::new(REFRESH_FILE).exists() {
-        fs::remove_file(REFRESH_FILE)?;
+    if config::get().refresh_file.exists() {
+        fs::remove_file(&config::get().refresh_file)?;
     }
     Ok(())
 }
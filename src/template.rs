@@ -0,0 +1,171 @@
+use crate::database::calendar::get_poke_exclusions;
+use crate::database::{get_fire_days, get_poke_stats, Poke};
+use crate::schedule::upcoming_runs;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use sqlx::SqlitePool;
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Values available to a job's detail template when it fires
+#[derive(Debug, Clone)]
+pub struct TemplateContext {
+    pub name: String,
+    pub now: DateTime<Utc>,
+    /// Number of times the job has fired, counting this notification
+    pub run_count: i64,
+    /// Consecutive days, up to today, on which the job fired
+    pub streak: i64,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// A placeholder found in a template
+enum Placeholder<'a> {
+    Name,
+    Now(&'a str),
+    Date(&'a str),
+    RunCount,
+    Streak,
+    NextRun(&'a str),
+    Env(&'a str),
+}
+
+/// Split a template into literal text and placeholders
+fn parse(template: &str) -> Result<Vec<Result<&str, Placeholder<'_>>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(Ok(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("Unclosed '{{{{' in template '{}'", template))?;
+        parts.push(Err(parse_placeholder(after[..end].trim())?));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Ok(rest));
+    }
+    Ok(parts)
+}
+
+fn parse_placeholder(text: &str) -> Result<Placeholder<'_>, String> {
+    let (key, arg) = match text.split_once(':') {
+        Some((key, arg)) => (key.trim(), Some(arg)),
+        None => (text, None),
+    };
+    let placeholder = match (key, arg) {
+        ("name", None) => Placeholder::Name,
+        ("run_count", None) => Placeholder::RunCount,
+        ("streak", None) => Placeholder::Streak,
+        ("now", arg) => Placeholder::Now(check_format(arg.unwrap_or(DEFAULT_TIME_FORMAT))?),
+        ("date", arg) => Placeholder::Date(check_format(arg.unwrap_or(DEFAULT_DATE_FORMAT))?),
+        ("next_run", arg) => {
+            Placeholder::NextRun(check_format(arg.unwrap_or(DEFAULT_TIME_FORMAT))?)
+        }
+        ("env", Some(var)) if !var.trim().is_empty() => Placeholder::Env(var.trim()),
+        ("env", _) => return Err("Template variable 'env' needs a name, e.g. {{env:USER}}".into()),
+        ("name" | "run_count" | "streak", Some(_)) => {
+            return Err(format!("Template variable '{}' takes no format", key))
+        }
+        _ => {
+            return Err(format!(
+                "Unknown template variable '{}', expected one of: name, now, date, run_count, \
+                 streak, next_run, env:VAR",
+                key
+            ))
+        }
+    };
+    Ok(placeholder)
+}
+
+fn check_format(format: &str) -> Result<&str, String> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format '{}' in template", format));
+    }
+    Ok(format)
+}
+
+/// Check that a template only uses known variables and valid date formats
+pub fn validate(template: &str) -> Result<(), String> {
+    parse(template).map(|_| ())
+}
+
+/// Render a template with the given values
+pub fn render(template: &str, context: &TemplateContext) -> Result<String, String> {
+    let now = context.now.with_timezone(&Local);
+    let mut output = String::new();
+    for part in parse(template)? {
+        match part {
+            Ok(text) => output.push_str(text),
+            Err(Placeholder::Name) => output.push_str(&context.name),
+            Err(Placeholder::Now(format) | Placeholder::Date(format)) => {
+                output.push_str(&now.format(format).to_string())
+            }
+            Err(Placeholder::RunCount) => output.push_str(&context.run_count.to_string()),
+            Err(Placeholder::Streak) => output.push_str(&context.streak.to_string()),
+            Err(Placeholder::NextRun(format)) => match context.next_run {
+                Some(next_run) => {
+                    output.push_str(&next_run.with_timezone(&Local).format(format).to_string())
+                }
+                None => output.push_str("none"),
+            },
+            // Unset variables render empty, they may only exist where the service runs
+            Err(Placeholder::Env(var)) => output.push_str(&std::env::var(var).unwrap_or_default()),
+        }
+    }
+    Ok(output)
+}
+
+/// Gather the template values for a job firing at `now`
+pub async fn context_for(
+    pool: &SqlitePool,
+    poke: &Poke,
+    now: DateTime<Utc>,
+) -> Result<TemplateContext, Box<dyn std::error::Error>> {
+    let stats = get_poke_stats(pool, poke.id).await?;
+    let exclusions = get_poke_exclusions(pool, poke.id).await?;
+    let next_run = upcoming_runs(poke, &exclusions, now, 1)
+        .ok()
+        .and_then(|runs| runs.first().copied());
+    let fire_days = get_fire_days(pool, poke.id).await?;
+
+    Ok(TemplateContext {
+        name: poke.name.clone(),
+        now,
+        run_count: stats.run_count + 1,
+        streak: streak(&fire_days, now.with_timezone(&Local).date_naive()),
+        next_run,
+    })
+}
+
+/// Count the consecutive days ending today that have a firing, with today
+/// counted as fired. `days` must be sorted most recent first
+fn streak(days: &[NaiveDate], today: NaiveDate) -> i64 {
+    let mut expected = today - Duration::days(1);
+    let mut count = 1;
+    for &day in days.iter().skip_while(|&&day| day >= today) {
+        if day != expected {
+            break;
+        }
+        count += 1;
+        expected -= Duration::days(1);
+    }
+    count
+}
+
+/// Render a job's detail for a notification firing now
+pub async fn render_detail(
+    pool: &SqlitePool,
+    poke: &Poke,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let detail = poke.detail.clone().unwrap_or_default();
+    if !detail.contains("{{") {
+        return Ok(detail);
+    }
+    let context = context_for(pool, poke, Utc::now()).await?;
+    Ok(render(&detail, &context)?)
+}
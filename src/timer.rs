@@ -0,0 +1,84 @@
+use crate::config;
+use crate::database::models::Timer;
+use crate::database::timer::{list_timers, take_due_timers, take_timer};
+use crate::schedule::format_duration;
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info};
+use notify_rust::Notification;
+use sqlx::SqlitePool;
+use std::io::Write;
+
+/// Seconds left until a timer fires
+pub fn remaining_secs(timer: &Timer, now: NaiveDateTime) -> i64 {
+    (timer.due_at - now).num_seconds().max(0)
+}
+
+/// Fire the timers that are due. Called by the service every tick
+pub async fn tick(pool: &SqlitePool) {
+    match take_due_timers(pool).await {
+        Ok(timers) => {
+            for timer in timers {
+                info!("Timer {} finished: {}", timer.id, timer.message);
+                announce(&timer);
+            }
+        }
+        Err(err) => error!("Failed to read timers: {}", err),
+    }
+}
+
+/// Count a timer down in the foreground when no service is running to fire
+/// it. Returns once the timer fires, is cancelled, or the wait is interrupted
+pub async fn wait_in_foreground(
+    pool: &SqlitePool,
+    timer: &Timer,
+) -> Result<(), Box<dyn std::error::Error>> {
+    loop {
+        let left = remaining_secs(timer, Utc::now().naive_utc());
+        print!("\rTimer {}: {} left   ", timer.id, format_duration(left));
+        std::io::stdout().flush()?;
+
+        if left == 0 {
+            println!();
+            // Another process (a service started meanwhile) may have fired it
+            if take_timer(pool, timer.id).await? {
+                announce(timer);
+                println!("Timer {} finished: {}", timer.id, timer.message);
+            }
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                take_timer(pool, timer.id).await?;
+                println!("Timer {} cancelled", timer.id);
+                return Ok(());
+            }
+        }
+
+        if !list_timers(pool)
+            .await?
+            .iter()
+            .any(|other| other.id == timer.id)
+        {
+            println!();
+            println!("Timer {} was cancelled", timer.id);
+            return Ok(());
+        }
+    }
+}
+
+/// Show the notification for a finished timer
+fn announce(timer: &Timer) {
+    let result = Notification::new()
+        .summary("Timer finished")
+        .body(&timer.message)
+        .appname(&config::get().app_name)
+        .icon("alarm-symbolic")
+        .hint(notify_rust::Hint::SoundName("alarm-clock-elapsed".into()))
+        .show();
+    if let Err(err) = result {
+        error!("Failed to display timer notification: {}", err);
+    }
+}
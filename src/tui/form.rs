@@ -0,0 +1,315 @@
+use crate::database::{Poke, Urgency};
+use crate::schedule::{upcoming_runs, Exclusions, SCHEDULE_PRESETS};
+use crate::template;
+use chrono::{DateTime, Local, Utc};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, Paragraph};
+use ratatui::Frame;
+
+/// Number of upcoming runs previewed under the schedule field
+const PREVIEW_RUNS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Schedule,
+    Detail,
+    Sound,
+    Urgency,
+}
+
+const FIELDS: [Field; 5] = [
+    Field::Name,
+    Field::Schedule,
+    Field::Detail,
+    Field::Sound,
+    Field::Urgency,
+];
+
+/// What a key press did to the form
+pub enum FormAction {
+    None,
+    Cancel,
+    Submit,
+}
+
+/// Popup form for adding a job or editing an existing one
+pub struct Form {
+    /// The job being edited, `None` when adding
+    pub editing: Option<Poke>,
+    pub name: String,
+    pub schedule: String,
+    pub detail: String,
+    pub sound: bool,
+    pub urgency: Urgency,
+    focus: usize,
+    /// Index of the last preset picked with F2
+    preset: Option<usize>,
+}
+
+impl Form {
+    pub fn add() -> Self {
+        Form {
+            editing: None,
+            name: String::new(),
+            schedule: String::new(),
+            detail: String::new(),
+            sound: false,
+            urgency: Urgency::default(),
+            focus: 0,
+            preset: None,
+        }
+    }
+
+    pub fn edit(poke: &Poke) -> Self {
+        Form {
+            name: poke.name.clone(),
+            schedule: poke.schedule_expr(),
+            detail: poke.detail.clone().unwrap_or_default(),
+            sound: poke.sound_enabled,
+            urgency: poke.urgency,
+            // The name cannot be changed, start on the schedule
+            focus: 1,
+            preset: None,
+            editing: Some(poke.clone()),
+        }
+    }
+
+    fn field(&self) -> Field {
+        FIELDS[self.focus]
+    }
+
+    fn move_focus(&mut self, forward: bool) {
+        loop {
+            self.focus = if forward {
+                (self.focus + 1) % FIELDS.len()
+            } else {
+                (self.focus + FIELDS.len() - 1) % FIELDS.len()
+            };
+            if !(self.editing.is_some() && self.field() == Field::Name) {
+                break;
+            }
+        }
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.field() {
+            Field::Name => Some(&mut self.name),
+            Field::Schedule => Some(&mut self.schedule),
+            Field::Detail => Some(&mut self.detail),
+            Field::Sound | Field::Urgency => None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormAction {
+        match key.code {
+            KeyCode::Esc => return FormAction::Cancel,
+            KeyCode::Enter => return FormAction::Submit,
+            KeyCode::Tab | KeyCode::Down => self.move_focus(true),
+            KeyCode::BackTab | KeyCode::Up => self.move_focus(false),
+            KeyCode::F(2) => {
+                // Cycle through common schedules instead of typing cron by hand
+                let next = self
+                    .preset
+                    .map_or(0, |index| (index + 1) % SCHEDULE_PRESETS.len());
+                self.preset = Some(next);
+                self.schedule = SCHEDULE_PRESETS[next].1.to_string();
+                self.focus = 1;
+            }
+            KeyCode::Char(' ') if self.field() == Field::Sound => self.sound = !self.sound,
+            KeyCode::Char(' ') | KeyCode::Right | KeyCode::Left
+                if self.field() == Field::Urgency =>
+            {
+                let forward = key.code != KeyCode::Left;
+                self.urgency = cycle_urgency(self.urgency, forward);
+            }
+            KeyCode::Backspace => {
+                if let Some(text) = self.text_mut() {
+                    text.pop();
+                }
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(text) = self.text_mut() {
+                    text.clear();
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(text) = self.text_mut() {
+                    text.push(c);
+                }
+            }
+            _ => {}
+        }
+        FormAction::None
+    }
+
+    /// Upcoming runs of the schedule being typed, or why it is invalid
+    fn preview(&self) -> Result<Vec<DateTime<Utc>>, String> {
+        if self.schedule.trim().is_empty() {
+            return Err("Type a schedule or press F2 for presets".to_string());
+        }
+        let poke = Poke::new("preview", self.schedule.as_str(), None, false)?;
+        upcoming_runs(&poke, &Exclusions::default(), Utc::now(), PREVIEW_RUNS)
+    }
+
+    /// Build the job to save, a modified copy when editing
+    pub fn to_poke(&self) -> Result<Poke, String> {
+        if self.name.trim().is_empty() {
+            return Err("Name cannot be empty".to_string());
+        }
+        let detail = (!self.detail.is_empty()).then(|| self.detail.clone());
+        let mut poke = match &self.editing {
+            Some(poke) => {
+                let mut poke = poke.clone();
+                poke.set_schedule(&self.schedule)?;
+                poke.detail = detail;
+                poke.sound_enabled = self.sound;
+                poke
+            }
+            None => Poke::new(
+                self.name.trim().to_string(),
+                self.schedule.clone(),
+                detail,
+                self.sound,
+            )?,
+        };
+        poke.urgency = self.urgency;
+        Ok(poke)
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let area = centered(frame.area(), 70, 16);
+        let title = if self.editing.is_some() {
+            " Edit job "
+        } else {
+            " Add job "
+        };
+        let block = Block::bordered().title(title);
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let [name, schedule, preview, detail, detail_check, sound, urgency, _, help] =
+            Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(PREVIEW_RUNS as u16 + 1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(2),
+            ])
+            .areas(inner);
+
+        let name_value = if self.editing.is_some() {
+            format!("{} (cannot be changed)", self.name)
+        } else {
+            self.name.clone()
+        };
+        self.draw_field(frame, name, Field::Name, "Name", &name_value);
+        self.draw_field(frame, schedule, Field::Schedule, "Schedule", &self.schedule);
+
+        let preview_lines: Vec<Line> = match self.preview() {
+            Ok(runs) if runs.is_empty() => vec![Line::styled(
+                "             never fires",
+                Style::default().fg(Color::Yellow),
+            )],
+            Ok(runs) => runs
+                .iter()
+                .map(|run| {
+                    Line::styled(
+                        format!(
+                            "             {}",
+                            run.with_timezone(&Local).format("%a %Y-%m-%d %H:%M")
+                        ),
+                        Style::default().fg(Color::Green),
+                    )
+                })
+                .collect(),
+            Err(err) => vec![Line::styled(
+                format!("             {}", err),
+                Style::default().fg(Color::Red),
+            )],
+        };
+        frame.render_widget(Paragraph::new(preview_lines), preview);
+
+        self.draw_field(frame, detail, Field::Detail, "Detail", &self.detail);
+        if let Err(err) = template::validate(&self.detail) {
+            frame.render_widget(
+                Paragraph::new(Line::styled(
+                    format!("             {}", err),
+                    Style::default().fg(Color::Red),
+                )),
+                detail_check,
+            );
+        }
+        let sound_value = if self.sound { "[x] on" } else { "[ ] off" };
+        self.draw_field(frame, sound, Field::Sound, "Sound", sound_value);
+        self.draw_field(
+            frame,
+            urgency,
+            Field::Urgency,
+            "Urgency",
+            &self.urgency.to_string(),
+        );
+
+        let help_text = vec![
+            Line::from("Tab/↑↓ move  F2 schedule presets  Space toggle  Ctrl-U clear"),
+            Line::from("Enter save  Esc cancel"),
+        ];
+        frame.render_widget(
+            Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray)),
+            help,
+        );
+    }
+
+    fn draw_field(&self, frame: &mut Frame, area: Rect, field: Field, label: &str, value: &str) {
+        let focused = self.field() == field;
+        let label_style = if focused {
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        let cursor = if focused && !matches!(field, Field::Sound | Field::Urgency) {
+            "▏"
+        } else {
+            ""
+        };
+        let line = Line::from(vec![
+            Span::styled(format!("{:<12} ", label), label_style),
+            Span::raw(value.to_string()),
+            Span::styled(cursor, Style::default().fg(Color::Cyan)),
+        ]);
+        frame.render_widget(Paragraph::new(line), area);
+    }
+}
+
+fn cycle_urgency(urgency: Urgency, forward: bool) -> Urgency {
+    const ORDER: [Urgency; 3] = [Urgency::Low, Urgency::Normal, Urgency::Critical];
+    let index = ORDER.iter().position(|u| *u == urgency).unwrap_or(1);
+    let next = if forward {
+        (index + 1) % ORDER.len()
+    } else {
+        (index + ORDER.len() - 1) % ORDER.len()
+    };
+    ORDER[next]
+}
+
+/// A rectangle of at most `width` x `height` in the middle of `area`
+pub fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,424 @@
+mod form;
+
+use crate::database::calendar::get_poke_exclusions;
+use crate::database::models::{FireRecord, PokeStats};
+use crate::database::{
+    add_poke, get_poke_history, get_poke_stats, list_pokes, pause_poke, remove_poke, resume_poke,
+    toggle_poke_sound, update_poke, Poke, PokeStatus,
+};
+use crate::notification::fire_poke;
+use crate::schedule::{format_duration, format_local, upcoming_runs};
+use crate::service::{is_service_running, signal_refresh};
+use chrono::{DateTime, Utc};
+use form::{centered, Form, FormAction};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
+use tokio_cron_scheduler::JobScheduler;
+
+/// How often jobs and history are read again from the database
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a status message stays in the footer
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of firings shown for the selected job
+const HISTORY_LIMIT: i64 = 20;
+
+/// A job with what the table shows about it
+struct JobRow {
+    poke: Poke,
+    stats: PokeStats,
+    next_run: Option<DateTime<Utc>>,
+}
+
+enum Mode {
+    Browse,
+    Form(Box<Form>),
+    /// Waiting for y/n before deleting the named job
+    ConfirmDelete(String),
+}
+
+struct App {
+    pool: SqlitePool,
+    /// Only used to check that added jobs can be scheduled
+    sched: JobScheduler,
+    rows: Vec<JobRow>,
+    table: TableState,
+    history: Vec<FireRecord>,
+    mode: Mode,
+    message: Option<(String, bool, Instant)>,
+    quit: bool,
+}
+
+/// Run the terminal interface until the user quits
+pub async fn run(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut app = App {
+        pool: pool.clone(),
+        sched: JobScheduler::new().await?,
+        rows: Vec::new(),
+        table: TableState::default(),
+        history: Vec::new(),
+        mode: Mode::Browse,
+        message: None,
+        quit: false,
+    };
+    app.reload().await?;
+
+    // Log lines would be drawn over the interface
+    let level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    log::set_max_level(level);
+    result
+}
+
+impl App {
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut last_reload = Instant::now();
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = RELOAD_INTERVAL.saturating_sub(last_reload.elapsed());
+            if tokio::task::block_in_place(|| event::poll(timeout))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key).await;
+                    }
+                }
+            }
+            if last_reload.elapsed() >= RELOAD_INTERVAL {
+                self.reload().await?;
+                last_reload = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Read jobs and the selected job's history again, keeping the selection
+    async fn reload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let selected = self.selected().map(|row| row.poke.name.clone());
+        let now = Utc::now();
+        let mut rows = Vec::new();
+        for poke in list_pokes(&self.pool, None).await? {
+            let stats = get_poke_stats(&self.pool, poke.id).await?;
+            let next_run = if poke.status == PokeStatus::Active {
+                let exclusions = get_poke_exclusions(&self.pool, poke.id).await?;
+                upcoming_runs(&poke, &exclusions, now, 1)
+                    .ok()
+                    .and_then(|runs| runs.first().copied())
+            } else {
+                None
+            };
+            rows.push(JobRow {
+                poke,
+                stats,
+                next_run,
+            });
+        }
+        // Soonest first, jobs that will not fire at the end
+        rows.sort_by_key(|row| (row.next_run.is_none(), row.next_run));
+        self.rows = rows;
+
+        let index = selected
+            .and_then(|name| self.rows.iter().position(|row| row.poke.name == name))
+            .or_else(|| self.table.selected())
+            .map(|index| index.min(self.rows.len().saturating_sub(1)));
+        self.table.select(if self.rows.is_empty() {
+            None
+        } else {
+            index.or(Some(0))
+        });
+
+        self.history = match self.selected() {
+            Some(row) => get_poke_history(&self.pool, row.poke.id, HISTORY_LIMIT).await?,
+            None => Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&JobRow> {
+        self.table.selected().and_then(|index| self.rows.get(index))
+    }
+
+    fn set_message(&mut self, message: impl Into<String>, error: bool) {
+        self.message = Some((message.into(), error, Instant::now()));
+    }
+
+    /// Report a change to a job, making sure the service reloads it
+    fn changed(&mut self, message: String) {
+        let message = if !is_service_running() {
+            format!("{} (the service is not running)", message)
+        } else if let Err(err) = signal_refresh() {
+            format!("{} (failed to notify the service: {})", message, err)
+        } else {
+            message
+        };
+        self.set_message(message, false);
+    }
+
+    async fn handle_key(&mut self, key: KeyEvent) {
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+        self.mode = match mode {
+            Mode::Browse => {
+                self.handle_browse_key(key).await;
+                // An action may have opened a form or confirmation
+                std::mem::replace(&mut self.mode, Mode::Browse)
+            }
+            Mode::Form(mut form) => match form.handle_key(key) {
+                FormAction::None => Mode::Form(form),
+                FormAction::Cancel => Mode::Browse,
+                FormAction::Submit => match self.save(&form).await {
+                    Ok(()) => Mode::Browse,
+                    Err(err) => {
+                        self.set_message(err, true);
+                        Mode::Form(form)
+                    }
+                },
+            },
+            Mode::ConfirmDelete(name) => {
+                if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    match remove_poke(&self.pool, &name).await {
+                        Ok(()) => self.changed(format!("Job '{}' moved to the trash", name)),
+                        Err(err) => self.set_message(err.to_string(), true),
+                    }
+                    self.reload_or_report().await;
+                }
+                Mode::Browse
+            }
+        };
+    }
+
+    async fn handle_browse_key(&mut self, key: KeyEvent) {
+        let name = self.selected().map(|row| row.poke.name.clone());
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.move_selection(1);
+                self.reload_or_report().await;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.move_selection(-1);
+                self.reload_or_report().await;
+            }
+            KeyCode::Char('a') => self.mode = Mode::Form(Box::new(Form::add())),
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(row) = self.selected() {
+                    self.mode = Mode::Form(Box::new(Form::edit(&row.poke)));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(name) = name {
+                    self.mode = Mode::ConfirmDelete(name);
+                }
+            }
+            KeyCode::Char('p') => {
+                let Some(row) = self.selected() else { return };
+                let result = if row.poke.status == PokeStatus::Paused {
+                    resume_poke(&self.pool, &row.poke.name)
+                        .await
+                        .map(|status| match status {
+                            PokeStatus::Completed => "ran out of runs and is now completed",
+                            _ => "resumed",
+                        })
+                } else {
+                    pause_poke(&self.pool, &row.poke.name)
+                        .await
+                        .map(|_| "paused")
+                };
+                match result {
+                    Ok(action) => self.changed(format!("Job '{}' {}", row.poke.name, action)),
+                    Err(err) => self.set_message(err.to_string(), true),
+                }
+                self.reload_or_report().await;
+            }
+            KeyCode::Char('s') => {
+                let Some(name) = name else { return };
+                match toggle_poke_sound(&self.pool, &name).await {
+                    Ok(enabled) => {
+                        let status = if enabled { "ON" } else { "OFF" };
+                        self.changed(format!("Sound toggled to {} for job '{}'", status, name));
+                    }
+                    Err(err) => self.set_message(err.to_string(), true),
+                }
+                self.reload_or_report().await;
+            }
+            KeyCode::Char('f') => {
+                let Some(row) = self.selected() else { return };
+                let pool = self.pool.clone();
+                let poke = row.poke.clone();
+                self.set_message(format!("Firing '{}'", poke.name), false);
+                tokio::spawn(async move { fire_poke(&pool, &poke, Utc::now(), 0).await });
+            }
+            _ => {}
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let index = (current + delta).clamp(0, self.rows.len() as isize - 1) as usize;
+        self.table.select(Some(index));
+    }
+
+    async fn save(&mut self, form: &Form) -> Result<(), String> {
+        let poke = form.to_poke()?;
+        let name = poke.name.clone();
+        let message = if form.editing.is_some() {
+            update_poke(&self.pool, &poke)
+                .await
+                .map_err(|err| err.to_string())?;
+            format!("Job '{}' updated", name)
+        } else {
+            add_poke(&self.pool, poke, &self.sched)
+                .await
+                .map_err(|err| err.to_string())?;
+            format!("Job '{}' added", name)
+        };
+        self.changed(message);
+        self.reload_or_report().await;
+        if let Some(index) = self.rows.iter().position(|row| row.poke.name == name) {
+            self.table.select(Some(index));
+        }
+        Ok(())
+    }
+
+    async fn reload_or_report(&mut self) {
+        if let Err(err) = self.reload().await {
+            self.set_message(err.to_string(), true);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [jobs, history, footer] = Layout::vertical([
+            Constraint::Min(6),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let now = Utc::now();
+        let header = Row::new(["Name", "Schedule", "Status", "Sound", "Next in", "Runs"])
+            .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.rows.iter().map(|row| {
+            let next_in = match row.next_run {
+                Some(next_run) => format_duration((next_run - now).num_seconds().max(0)),
+                None => "-".to_string(),
+            };
+            let runs = match row.poke.max_runs {
+                Some(max_runs) => format!("{}/{}", row.stats.run_count, max_runs),
+                None => row.stats.run_count.to_string(),
+            };
+            let status_style = match row.poke.status {
+                PokeStatus::Active => Style::default().fg(Color::Green),
+                PokeStatus::Paused => Style::default().fg(Color::Yellow),
+                _ => Style::default().fg(Color::DarkGray),
+            };
+            Row::new([
+                Cell::from(row.poke.name.clone()),
+                Cell::from(row.poke.schedule_expr()),
+                Cell::from(row.poke.status.to_string()).style(status_style),
+                Cell::from(if row.poke.sound_enabled { "on" } else { "off" }),
+                Cell::from(next_in),
+                Cell::from(runs),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(25),
+                Constraint::Percentage(30),
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(14),
+                Constraint::Length(8),
+            ],
+        )
+        .header(header)
+        .block(Block::bordered().title(format!(" Jobs ({}) ", self.rows.len())))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, jobs, &mut self.table);
+
+        let title = match self.selected() {
+            Some(row) => format!(" History of '{}' ", row.poke.name),
+            None => " History ".to_string(),
+        };
+        let lines: Vec<Line> = if self.history.is_empty() {
+            vec![Line::styled(
+                "Never fired",
+                Style::default().fg(Color::DarkGray),
+            )]
+        } else {
+            self.history
+                .iter()
+                .map(|record| {
+                    let acked = match record.acked_at {
+                        Some(acked_at) => format!("acknowledged {}", format_local(acked_at)),
+                        None => "not acknowledged".to_string(),
+                    };
+                    let missed = if record.missed > 0 {
+                        format!(" ({} missed)", record.missed)
+                    } else {
+                        String::new()
+                    };
+                    Line::from(format!(
+                        "{}  {}{}",
+                        format_local(record.fired_at),
+                        acked,
+                        missed
+                    ))
+                })
+                .collect()
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            history,
+        );
+
+        let footer_line = match &self.message {
+            Some((message, error, at)) if at.elapsed() < MESSAGE_TIMEOUT => {
+                let color = if *error { Color::Red } else { Color::Green };
+                Line::styled(message.clone(), Style::default().fg(color))
+            }
+            _ => Line::styled(
+                "a add  e edit  p pause/resume  f fire  s sound  d delete  j/k move  q quit",
+                Style::default().fg(Color::DarkGray),
+            ),
+        };
+        frame.render_widget(Paragraph::new(footer_line), footer);
+
+        match &self.mode {
+            Mode::Browse => {}
+            Mode::Form(form) => form.draw(frame),
+            Mode::ConfirmDelete(name) => {
+                let area = centered(frame.area(), 50, 5);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(vec![
+                        Line::from(format!("Move job '{}' to the trash?", name)),
+                        Line::from(""),
+                        Line::styled(
+                            "y move to trash  any other key cancels",
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ])
+                    .block(Block::bordered().title(" Confirm ")),
+                    area,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::database::{get_poke_by_name, Poke};
+use crate::display::display_upcoming;
+use crate::schedule::{upcoming_runs, Exclusions};
+use crate::template;
+use chrono::{NaiveTime, Timelike, Utc};
+use sqlx::SqlitePool;
+use std::io::{self, BufRead, Write};
+
+/// Number of fire times previewed before saving
+const PREVIEW_RUNS: usize = 5;
+
+/// What the wizard asked for, before the remaining `add` options are applied
+pub struct WizardAnswers {
+    pub name: String,
+    pub schedule: String,
+    pub detail: Option<String>,
+    pub sound: bool,
+}
+
+/// Print a prompt and read one trimmed line
+fn ask(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err("Input ended before the job was complete".into());
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Ask until `parse` accepts the answer, printing why it did not
+fn ask_until<T>(
+    prompt: &str,
+    mut parse: impl FnMut(&str) -> Result<T, String>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    loop {
+        match parse(&ask(prompt)?) {
+            Ok(value) => return Ok(value),
+            Err(err) => println!("  {}", err),
+        }
+    }
+}
+
+pub fn ask_yes_no(prompt: &str, default: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    ask_until(&format!("{} {} ", prompt, hint), |answer| {
+        match answer.to_lowercase().as_str() {
+            "" => Ok(default),
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err("Please answer y or n".to_string()),
+        }
+    })
+}
+
+fn parse_time(answer: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(answer, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(&format!("{}:00", answer), "%H:%M"))
+        .map_err(|_| format!("Invalid time '{}', expected HH:MM", answer))
+}
+
+/// Check that a schedule follows the `Poke::new` rules and fires at least once
+fn check_schedule(schedule: &str) -> Result<String, String> {
+    let poke = Poke::new("wizard", schedule, None, false)?;
+    if poke.is_manual() {
+        return Ok(schedule.to_string());
+    }
+    let runs = upcoming_runs(&poke, &Exclusions::default(), Utc::now(), 1)?;
+    if runs.is_empty() {
+        return Err(format!("Schedule '{}' never fires", schedule));
+    }
+    Ok(schedule.to_string())
+}
+
+/// Ask for a schedule, offering common presets before a raw expression
+fn ask_schedule() -> Result<String, Box<dyn std::error::Error>> {
+    println!("Schedule:");
+    println!("  1) Every hour");
+    println!("  2) Every day at ...");
+    println!("  3) Every weekday at ...");
+    println!("  4) Every N minutes");
+    println!("  5) Custom: cron expression, @daily, RRULE, \"every 45m\" or \"manual\"");
+    // Times of day become RRULEs, which run in local time unlike cron
+    loop {
+        let schedule = match ask("Choose 1-5: ")?.as_str() {
+            "1" => "FREQ=HOURLY".to_string(),
+            "2" => {
+                let time = ask_until("Time (HH:MM): ", parse_time)?;
+                format!(
+                    "FREQ=DAILY;BYHOUR={};BYMINUTE={}",
+                    time.hour(),
+                    time.minute()
+                )
+            }
+            "3" => {
+                let time = ask_until("Time (HH:MM): ", parse_time)?;
+                format!(
+                    "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;BYHOUR={};BYMINUTE={}",
+                    time.hour(),
+                    time.minute()
+                )
+            }
+            "4" => ask_until("Minutes between notifications: ", |answer| {
+                match answer.parse::<u32>() {
+                    Ok(minutes) if minutes > 0 => Ok(format!("every {}m", minutes)),
+                    _ => Err(format!("Invalid number of minutes '{}'", answer)),
+                }
+            })?,
+            "5" => ask_until("Expression: ", check_schedule)?,
+            _ => {
+                println!("  Please choose a number from 1 to 5");
+                continue;
+            }
+        };
+        match check_schedule(&schedule) {
+            Ok(schedule) => return Ok(schedule),
+            Err(err) => println!("  {}", err),
+        }
+    }
+}
+
+/// Ask step by step for the basic settings of a new job. A name given on the
+/// command line is used as is
+pub async fn ask_job(
+    pool: &SqlitePool,
+    name: Option<String>,
+) -> Result<WizardAnswers, Box<dyn std::error::Error>> {
+    let name = match name {
+        Some(name) => name,
+        None => loop {
+            let name = ask("Name: ")?;
+            if name.is_empty() {
+                println!("  Name cannot be empty");
+            } else if get_poke_by_name(pool, &name).await.is_ok() {
+                println!("  A job with name '{}' already exists", name);
+            } else {
+                break name;
+            }
+        },
+    };
+    let schedule = ask_schedule()?;
+    let detail = ask_until("Detail (optional): ", |answer| {
+        template::validate(answer)?;
+        Ok((!answer.is_empty()).then(|| answer.to_string()))
+    })?;
+    let sound = ask_yes_no("Play a sound?", false)?;
+    Ok(WizardAnswers {
+        name,
+        schedule,
+        detail,
+        sound,
+    })
+}
+
+/// Show when the job would fire and ask whether to save it
+pub fn confirm(poke: &Poke) -> Result<bool, Box<dyn std::error::Error>> {
+    println!();
+    if poke.is_manual() {
+        println!("'{}' only fires as a follow-up of another job", poke.name);
+    } else {
+        let runs = upcoming_runs(poke, &Exclusions::default(), Utc::now(), PREVIEW_RUNS)?;
+        display_upcoming(
+            &runs
+                .into_iter()
+                .map(|time| (time, poke.name.clone()))
+                .collect::<Vec<_>>(),
+        );
+    }
+    println!();
+    ask_yes_no("Save this job?", true)
+}